@@ -5,6 +5,10 @@ fn has_env(env: &str) -> bool {
 }
 
 fn main() {
+	for cfg in ["envoy_tests", "envoy_auth_tests", "cloud_oauth_tests", "cloud_preauth_tests"] {
+		println!("cargo:rustc-check-cfg=cfg({cfg})");
+	}
+
 	println!("cargo:rerun-if-env-changed=ENVOY_URL");
 	if (has_env("ENVOY_URL")) {
 		println!("cargo:rustc-cfg=envoy_tests");
@@ -1,36 +1,107 @@
+use std::future::Future;
+use std::io;
 use std::sync::Arc;
 
-use arcstr::ArcStr;
-use base64::engine::Engine;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
 use serde::Deserialize;
 use serde::Serialize;
-use tokio::sync::Mutex;
+
+use crate::credentials::CredentialError;
+use crate::credentials::CredentialProvider;
+use crate::credentials::Credentials;
 
 mod connection_type;
 pub use connection_type::ConnectionType;
 pub use connection_type::InvalidConnectionType;
+mod devices;
+pub use devices::Device;
+pub use devices::Devices;
+pub use devices::Gateway;
+pub use devices::Meter;
+mod ev_charger;
+pub use ev_charger::ChargingSession;
+pub use ev_charger::EvCharger;
+pub use ev_charger::EvChargerStatus;
+mod events;
+pub use events::Alarm;
+pub use events::Event;
+use events::EventTypesResponse;
+pub use events::EventType;
+mod governor;
+pub use governor::FileQuotaStore;
+pub use governor::Governor;
+pub use governor::Limits;
+pub use governor::MonthlyUsage;
+pub use governor::Overflow;
+pub use governor::Quota;
+pub use governor::QuotaStore;
 mod granularity;
 pub use granularity::Granularity;
 pub use granularity::InvalidGranularity;
+#[cfg(any(test, feature = "mock"))] pub mod mock;
+pub mod oauth;
+mod page;
+pub use page::Page;
+mod session;
+use session::Session;
+mod settings;
+pub use settings::BatteryProfile;
+pub use settings::BatterySettings;
+#[cfg(feature = "control")] pub use settings::BatterySettingsUpdate;
+pub use settings::GridStatus;
+pub use settings::LoadControlCircuit;
+pub use settings::StormGuard;
 mod system;
 pub use system::Address;
-use system::ListSystemsResponse;
+pub use system::DailyEnergy;
+pub use system::LifetimeEnergy;
 pub use system::Metadata;
-pub use system::MicroinverterProduction;
+use system::RetrieveSystemIdResponse;
 pub use system::System;
 pub use system::SystemSummary;
+use system::SystemResponse;
+mod telemetry;
+pub use telemetry::BatteryEnergy;
+pub use telemetry::BatteryInterval;
+pub use telemetry::BatteryReading;
+pub use telemetry::ConsumptionMeterInterval;
+pub use telemetry::GridInterval;
+pub use telemetry::LatestTelemetry;
+pub use telemetry::MicroinverterProduction;
+pub use telemetry::ProductionMeterInterval;
+pub use telemetry::Reading;
+pub use telemetry::StateOfCharge;
+mod token_store;
+pub use token_store::FileTokenStore;
+pub use token_store::MemoryTokenStore;
+pub use token_store::TokenStore;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.enphaseenergy.com";
+/// The largest page size the v4 API allows.
+pub const MAX_PAGE_SIZE: u32 = 100;
+pub const DEFAULT_PAGE_SIZE: u32 = MAX_PAGE_SIZE;
 
 #[cfg(feature = "clap")]
 #[derive(Debug, clap::Parser)]
 pub struct Config {
+	#[clap(long, env = "ENPHASE_API_BASE_URL", default_value = DEFAULT_BASE_URL)]
+	base_url: String,
 	#[clap(long, env = "ENPHASE_API_KEY")]
 	api_key: String,
 	#[clap(long, env = "ENPHASE_CLIENT_ID")]
 	client_id: String,
-	#[clap(long, env = "ENPHASE_CLIENT_SECRET")]
-	client_secret: String,
+	#[clap(long, env = "ENPHASE_CLIENT_SECRET", required_unless_present_any = ["client_secret_file", "client_secret_command"])]
+	client_secret: Option<String>,
+	#[clap(long, env = "ENPHASE_CLIENT_SECRET_FILE", conflicts_with_all = ["client_secret", "client_secret_command"])]
+	client_secret_file: Option<std::path::PathBuf>,
+	#[clap(long, env = "ENPHASE_CLIENT_SECRET_COMMAND", conflicts_with_all = ["client_secret", "client_secret_file"])]
+	client_secret_command: Option<String>,
 	#[clap(long, env = "ENPHASE_OAUTH_CODE")]
 	code: Option<String>,
+	#[clap(long, env = "ENPHASE_OAUTH_REDIRECT_URI", default_value = oauth::DEFAULT_REDIRECT_URI)]
+	redirect_uri: String,
 	#[clap(long, env = "ENPHASE_ACCESS_TOKEN")]
 	access_token: Option<String>,
 	#[clap(long, env = "ENPHASE_REFRESH_TOKEN")]
@@ -40,114 +111,249 @@ pub struct Config {
 #[cfg(feature = "clap")]
 impl Config {
 	#[inline]
-	pub async fn client(&self) -> Result<Client, reqwest::Error> {
+	fn credential_provider(&self) -> Arc<dyn CredentialProvider> {
+		match (&self.client_secret, &self.client_secret_file, &self.client_secret_command) {
+			(_, Some(path), _) => Arc::new(crate::credentials::FileProvider::new(&self.client_id, path)),
+			(_, _, Some(command)) => Arc::new(crate::credentials::CommandProvider::shell(&self.client_id, command)),
+			(secret, None, None) => Arc::new(Credentials::new(&self.client_id, secret.as_deref().unwrap_or_default()))
+		}
+	}
+
+	#[inline]
+	pub async fn client(&self) -> Result<Client, Error> {
 		match (self.code.as_ref(), self.access_token.as_ref(), self.refresh_token.as_ref()) {
-			(Some(code), None, None) => Client::oauth(&self.api_key, self.client_id.clone(), self.client_secret.clone(), code).await,
-			(None, Some(access_token), Some(refresh_token)) => Ok(Client::preauth(&self.api_key, self.client_id.clone(), self.client_secret.clone(), access_token.to_owned(), refresh_token.to_owned())),
+			(Some(code), None, None) => Client::oauth_with_base_url(&self.base_url, &self.api_key, self.credential_provider(), code, &self.redirect_uri).await,
+			(None, Some(access_token), Some(refresh_token)) => Ok(Client::preauth_with_credential_provider(&self.api_key, self.credential_provider(), access_token.to_owned(), refresh_token.to_owned())?.with_base_url(&self.base_url)),
 			(Some(_), Some(_), Some(_)) => todo!("Error for having both code and tokens set"),
 			_ => todo!("Error for not having either code or both tokens set")
 		}
 	}
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("HTTP error: {0}")]
+	Http(#[from] reqwest::Error),
+	#[error("Failed to retrieve client credentials: {0}")]
+	Credentials(#[from] CredentialError),
+	#[error("Token store error: {0}")]
+	TokenStore(io::Error),
+	#[error("Token store is empty")]
+	NoStoredTokens,
+	#[error("Rate limited; retry after {0:?}")]
+	RateLimited(Option<std::time::Duration>),
+	#[error("Invalid timezone \"{0}\"")]
+	InvalidTimezone(String),
+	#[error("Monthly request quota exhausted")]
+	QuotaExhausted,
+	#[error("Quota store error: {0}")]
+	QuotaStore(io::Error),
+	#[cfg(feature = "oauth-listener")]
+	#[error("OAuth redirect listener error: {0}")]
+	Listener(#[from] oauth::ListenerError)
+}
+
+/// Fetches 1-based pages with `fetch` until [`Page::total`] is reached or a page comes back
+/// empty.
+pub(crate) async fn all_pages<T, F, Fut>(mut fetch: F) -> Result<Vec<T>, Error>
+where
+	F: FnMut(u32) -> Fut,
+	Fut: Future<Output = Result<Page<T>, Error>>
+{
+	let mut items = Vec::new();
+	for page in 1.. {
+		let page = fetch(page).await?;
+		let done = page.items.is_empty() || items.len() + page.items.len() >= page.total as usize;
+		items.extend(page.items);
+		if (done) {
+			break;
+		}
+	}
+	Ok(items)
+}
+
 pub struct Client {
-	client: reqwest::Client,
-	api_key_qstr: ArcStr,
-	//client_id: String,
-	//client_secret: String,
-	token_auth_header: ArcStr,
-	auth_header: Arc<Mutex<String>>,
-	access_token: String,
-	refresh_token: String
+	session: Arc<Session>
 }
 
 impl Client {
-	pub async fn oauth(api_key: &str, client_id: String, client_secret: String, code: &str) -> Result<Self, reqwest::Error> {
+	pub async fn oauth(api_key: &str, client_id: String, client_secret: String, code: &str) -> Result<Self, Error> {
+		Self::oauth_with_credential_provider(api_key, Arc::new(Credentials::new(client_id, client_secret)), code).await
+	}
+
+	/// Like [`Client::oauth`], but the client ID and secret come from `provider`, which is
+	/// re-queried whenever the token endpoint rejects them.
+	pub async fn oauth_with_credential_provider(api_key: &str, provider: Arc<dyn CredentialProvider>, code: &str) -> Result<Self, Error> {
+		Self::oauth_with_redirect_uri(api_key, provider, code, oauth::DEFAULT_REDIRECT_URI).await
+	}
+
+	/// Exchanges a code that was issued for `redirect_uri`, which must match the one passed to
+	/// [`oauth::authorize_url`].
+	pub async fn oauth_with_redirect_uri(api_key: &str, provider: Arc<dyn CredentialProvider>, code: &str, redirect_uri: &str) -> Result<Self, Error> {
+		Self::oauth_with_base_url(DEFAULT_BASE_URL, api_key, provider, code, redirect_uri).await
+	}
+
+	/// Like [`Client::oauth_with_redirect_uri`], but talks to the API at `base_url` instead of
+	/// [`DEFAULT_BASE_URL`].
+	pub async fn oauth_with_base_url(base_url: &str, api_key: &str, provider: Arc<dyn CredentialProvider>, code: &str, redirect_uri: &str) -> Result<Self, Error> {
 		let client = reqwest::Client::new();
-		let url = format!("https://api.enphaseenergy.com/oauth/token?grant_type=authorization_code&redirect_uri=https://api.enphaseenergy.com/oauth/redirect_uri&code={code}");
-		let token_auth_header = Self::token_auth_header(&client_id, &client_secret);
-		let response: AuthResponse = client.post(url).header("Authorization", &*token_auth_header).send().await?.error_for_status()?.json().await?;
-		let auth_header = Arc::new(Mutex::new(format!("Bearer {}", &response.access_token)));
+		let mut credentials = crate::credentials::query(&provider).await?;
+		let url = format!("{}/oauth/token", base_url.trim_end_matches('/'));
+		let query = url::form_urlencoded::Serializer::new(String::new())
+			.append_pair("grant_type", "authorization_code")
+			.append_pair("redirect_uri", redirect_uri)
+			.append_pair("code", code)
+			.finish();
+		let response = Session::request_tokens(&client, &format!("{url}?{query}"), &provider, &mut credentials).await?;
 		Ok(Self {
-			client,
-			api_key_qstr: format!("key={api_key}").into(),
-			//client_id,
-			//client_secret,
-			token_auth_header,
-			auth_header,
-			access_token: response.access_token,
-			refresh_token: response.refresh_token
+			session: Arc::new(Session::new(client, base_url, api_key, provider, credentials, Tokens::from(response)))
 		})
 	}
 
+	/// Waits for `listener` to receive the redirect for an authorization started with
+	/// [`oauth::RedirectListener::authorize_url`], then exchanges the code.
+	#[cfg(feature = "oauth-listener")]
+	pub async fn oauth_with_listener(api_key: &str, provider: Arc<dyn CredentialProvider>, listener: oauth::RedirectListener, state: &str) -> Result<Self, Error> {
+		Self::oauth_with_listener_and_base_url(DEFAULT_BASE_URL, api_key, provider, listener, state).await
+	}
+
+	/// Like [`Client::oauth_with_listener`], but talks to the API at `base_url`; start the
+	/// authorization with [`oauth::RedirectListener::authorize_url_with_base_url`].
+	#[cfg(feature = "oauth-listener")]
+	pub async fn oauth_with_listener_and_base_url(base_url: &str, api_key: &str, provider: Arc<dyn CredentialProvider>, listener: oauth::RedirectListener, state: &str) -> Result<Self, Error> {
+		let redirect_uri = listener.redirect_uri().to_owned();
+		let code = listener.accept_code(state).await?;
+		Self::oauth_with_base_url(base_url, api_key, provider, &code, &redirect_uri).await
+	}
+
 	pub fn preauth(api_key: &str, client_id: String, client_secret: String, access_token: String, refresh_token: String) -> Self {
-		let token_auth_header = Self::token_auth_header(&client_id, &client_secret);
-		let auth_header = Arc::new(Mutex::new(format!("Bearer {}", &access_token)));
+		let credentials = Credentials::new(client_id, client_secret);
+		let tokens = Tokens::new(access_token, refresh_token, None);
 		Self {
-			client: reqwest::Client::new(),
-			api_key_qstr: format!("key={api_key}").into(),
-			//client_id,
-			//client_secret,
-			token_auth_header,
-			auth_header,
-			access_token,
-			refresh_token
+			session: Arc::new(Session::new(reqwest::Client::new(), DEFAULT_BASE_URL, api_key, Arc::new(credentials.clone()), credentials, tokens))
 		}
 	}
 
+	/// Like [`Client::preauth`], but the client ID and secret come from `provider`, which is
+	/// re-queried whenever the token endpoint rejects them.
+	pub fn preauth_with_credential_provider(api_key: &str, provider: Arc<dyn CredentialProvider>, access_token: String, refresh_token: String) -> Result<Self, CredentialError> {
+		let credentials = provider.credentials()?;
+		let tokens = Tokens::new(access_token, refresh_token, None);
+		Ok(Self {
+			session: Arc::new(Session::new(reqwest::Client::new(), DEFAULT_BASE_URL, api_key, provider, credentials, tokens))
+		})
+	}
+
+	/// Resumes from tokens previously saved to `store`, which continues to receive every
+	/// refreshed set of tokens.
+	pub fn from_token_store(api_key: &str, provider: Arc<dyn CredentialProvider>, store: Arc<dyn TokenStore>) -> Result<Self, Error> {
+		let tokens = store.load().map_err(Error::TokenStore)?.ok_or(Error::NoStoredTokens)?;
+		let credentials = provider.credentials()?;
+		let session = Session::new(reqwest::Client::new(), DEFAULT_BASE_URL, api_key, provider, credentials, tokens);
+		session.set_token_store(store)?;
+		Ok(Self { session: Arc::new(session) })
+	}
+
+	/// Sends all further requests, including token refreshes, to `base_url` instead of
+	/// [`DEFAULT_BASE_URL`].
+	pub fn with_base_url(self, base_url: &str) -> Self {
+		self.session.set_base_url(base_url);
+		self
+	}
+
+	/// Points this client, including token refreshes, at a
+	/// [`vcr::Recorder`](crate::vcr::Recorder) forwarding to its current base URL, or at a
+	/// [`vcr::Player`](crate::vcr::Player) replaying a cassette.  Keep the returned
+	/// [`Vcr`](crate::vcr::Vcr) alive while the client is in use.
+	#[cfg(feature = "vcr")]
+	pub async fn with_vcr(self, mode: crate::vcr::Mode) -> io::Result<(Self, crate::vcr::Vcr)> {
+		let vcr = crate::vcr::Vcr::start(mode, &self.session.base_url()).await?;
+		Ok((self.with_base_url(&vcr.base_url()), vcr))
+	}
+
+	/// Routes every API request through `governor`, which should be shared by all clients
+	/// using the same API key.
+	pub fn with_governor(self, governor: Arc<Governor>) -> Self {
+		self.session.set_governor(governor);
+		self
+	}
+
+	/// Sets how many items to request per page from paginated endpoints, clamped to between 1
+	/// and [`MAX_PAGE_SIZE`].
+	pub fn with_page_size(self, page_size: u32) -> Self {
+		self.session.set_page_size(page_size.clamp(1, MAX_PAGE_SIZE));
+		self
+	}
+
+	/// Saves the current tokens to `store`, and every refreshed set of tokens after that.
+	pub fn with_token_store(self, store: Arc<dyn TokenStore>) -> Result<Self, Error> {
+		self.session.set_token_store(store)?;
+		Ok(self)
+	}
+
 	pub fn tokens(&self) -> Tokens {
-		Tokens {
-			access: self.access_token.clone(),
-			refresh: self.refresh_token.clone()
-		}
+		self.session.tokens()
+	}
+
+	/// Exchanges the refresh token for new tokens.  Concurrent calls, including refreshes
+	/// triggered by [`System`] methods, share a single request to the token endpoint.
+	pub async fn refresh(&self) -> Result<Tokens, Error> {
+		self.session.refresh(&self.session.tokens().access).await
+	}
+
+	/// Lists every system the account can see, fetching as many pages as necessary.
+	pub async fn list_systems(&self) -> Result<Vec<System>, Error> {
+		all_pages(|page| self.list_systems_page(page)).await
 	}
 
-	pub async fn refresh(&mut self) -> Result<Tokens, reqwest::Error> {
-		let response: AuthResponse = self
-			.client
-			.post(format!("https://api.enphaseenergy.com/oauth/token?grant_type=refresh_token&refresh_token={}", self.refresh_token))
-			.header("Authorization", &*self.token_auth_header)
-			.send()
-			.await?
-			.error_for_status()?
-			.json()
+	pub async fn get_system(&self, system_id: u32) -> Result<System, Error> {
+		let response: SystemResponse = self
+			.session
+			.get(&format!("{}/api/v4/systems/{system_id}?{}", self.session.base_url(), self.session.api_key_qstr))
 			.await?;
-		*self.auth_header.lock().await = format!("Bearer {}", &response.access_token);
-		self.access_token = response.access_token;
-		self.refresh_token = response.refresh_token;
-		Ok(self.tokens())
-	}
-
-	pub async fn list_systems(&self) -> Result<Vec<System>, reqwest::Error> {
-		let response: ListSystemsResponse = self
-			.client
-			.get(format!("https://api.enphaseenergy.com/api/v4/systems?{}", self.api_key_qstr))
-			.header("Authorization", &*self.auth_header.lock().await)
-			.send()
-			.await?
-			.error_for_status()?
-			.json()
+		Ok(System::from((self.session.clone(), response)))
+	}
+
+	/// Finds the system whose Envoy has serial number `serial`, i.e. [`crate::envoy::DeviceMetadata::serial_number`].
+	pub async fn find_system_by_envoy_serial(&self, serial: &str) -> Result<Option<System>, Error> {
+		let url = format!(
+			"{}/api/v4/systems/retrieve_system_id?{}&serial_num={}",
+			self.session.base_url(),
+			self.session.api_key_qstr,
+			url::form_urlencoded::byte_serialize(serial.as_bytes()).collect::<String>()
+		);
+		let response: RetrieveSystemIdResponse = match self.session.get(&url).await {
+			Ok(response) => response,
+			Err(Error::Http(e)) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => return Ok(None),
+			Err(e) => return Err(e)
+		};
+		self.get_system(response.system_id).await.map(Some)
+	}
+
+	/// Definitions of the event types referred to by [`Event::event_type_id`] and
+	/// [`Alarm::event_type_id`].
+	pub async fn get_event_types(&self) -> Result<Vec<EventType>, Error> {
+		let response: EventTypesResponse = self
+			.session
+			.get(&format!("{}/api/v4/systems/event_types?{}", self.session.base_url(), self.session.api_key_qstr))
 			.await?;
-		let response = response
-			.systems
-			.into_iter()
-			.map(|s| System::from((self.client.clone(), self.api_key_qstr.clone(), self.auth_header.clone(), s)))
-			.collect();
-		Ok(response)
+		Ok(response.event_types)
 	}
 
-	#[inline]
-	fn token_auth_header(client_id: &str, client_secret: &str) -> ArcStr {
-		format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("{client_id}:{client_secret}"))).into()
+	/// Fetches a single 1-based page of systems.
+	pub async fn list_systems_page(&self, page: u32) -> Result<Page<System>, Error> {
+		let url = format!("{}/api/v4/systems?{}&page={page}&size={}", self.session.base_url(), self.session.api_key_qstr, self.session.page_size());
+		let response: Page<SystemResponse> = self.session.get(&url).await?;
+		Ok(response.map(|s| System::from((self.session.clone(), s))))
 	}
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct AuthResponse {
+pub(crate) struct AuthResponse {
 	access_token: String,
 	//token_type: CompactString,
-	refresh_token: String
-	//expires_in: u32,
+	refresh_token: String,
+	expires_in: i64
 	//scope: CompactString,
 	//enl_uid: CompactString,
 	//enl_cid: CompactString,
@@ -157,10 +363,43 @@ struct AuthResponse {
 	//jti: Uuid
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Tokens {
 	access: String,
-	refresh: String
+	refresh: String,
+	#[serde(default)]
+	expires_at: Option<DateTime<Utc>>
+}
+
+impl Tokens {
+	#[inline]
+	pub fn new(access: impl Into<String>, refresh: impl Into<String>, expires_at: Option<DateTime<Utc>>) -> Self {
+		Self { access: access.into(), refresh: refresh.into(), expires_at }
+	}
+
+	#[inline]
+	pub fn access(&self) -> &str {
+		&self.access
+	}
+
+	#[inline]
+	pub fn refresh(&self) -> &str {
+		&self.refresh
+	}
+
+	/// When the access token expires, if known.  Tokens supplied to [`Client::preauth`] have
+	/// no known expiry.
+	#[inline]
+	pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+		self.expires_at
+	}
+}
+
+impl From<AuthResponse> for Tokens {
+	#[inline]
+	fn from(response: AuthResponse) -> Self {
+		Self::new(response.access_token, response.refresh_token, Some(Utc::now() + Duration::seconds(response.expires_in)))
+	}
 }
 
 #[cfg(test)]
@@ -200,7 +439,7 @@ mod test {
 	/// and generating them programmatically is not practical.  As a result, we have to do this
 	/// unfortunate single test for all methods when logging in with OAUTH.
 	async fn test_oauth_complete() {
-		let mut client = client_oauth().await;
+		let client = client_oauth().await;
 		client.refresh().await.unwrap();
 		client.list_systems().await.unwrap();
 	}
@@ -208,10 +447,27 @@ mod test {
 	#[tokio::test]
 	#[cfg_attr(not(cloud_preauth_tests), ignore)]
 	async fn test_preauth_refresh() {
-		let mut client = client_preauth();
+		let client = client_preauth();
 		client.refresh().await.unwrap();
 	}
 
+	#[tokio::test]
+	#[cfg_attr(not(cloud_preauth_tests), ignore)]
+	async fn test_preauth_concurrent_refresh() {
+		let client = client_preauth();
+		let (first, second) = tokio::join!(client.refresh(), client.refresh());
+		assert_eq!(first.unwrap().access(), second.unwrap().access());
+	}
+
+	#[tokio::test]
+	async fn test_refresh_skipped_when_already_refreshed() {
+		let client = Client::preauth("key", "id".into(), "secret".into(), "access-2".into(), "refresh-2".into());
+		// A caller still holding a replaced access token gets the current tokens without another
+		// token request.
+		let tokens = client.session.refresh("access-1").await.unwrap();
+		assert_eq!(tokens.access(), "access-2");
+	}
+
 	#[tokio::test]
 	#[cfg_attr(not(cloud_preauth_tests), ignore)]
 	async fn test_preauth_list_systems() {
@@ -247,7 +503,7 @@ mod test {
 	#[tokio::test]
 	#[cfg_attr(not(cloud_preauth_tests), ignore)]
 	async fn test_preauth_system_calls_with_token_refresh() {
-		let mut client = client_preauth();
+		let client = client_preauth();
 		client.refresh().await.unwrap();
 		let system = client.list_systems().await.unwrap().pop().unwrap();
 		client.refresh().await.unwrap();
@@ -257,4 +513,212 @@ mod test {
 		client.refresh().await.unwrap();
 		system.get_microinverter_production(&NaiveDate::from_ymd_opt(2021, 12, 25).unwrap(), None).await.unwrap();
 	}
+
+	#[cfg(feature = "oauth-listener")]
+	#[tokio::test]
+	async fn test_mock_oauth_with_listener() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let provider = Arc::new(Credentials::new(mock::CLIENT_ID, mock::CLIENT_SECRET));
+		let listener = oauth::RedirectListener::bind("127.0.0.1:0").await.unwrap();
+		let authorize_url = listener.authorize_url_with_base_url(&server.base_url(), mock::CLIENT_ID, "xyz").unwrap();
+		assert!(authorize_url.as_str().starts_with(&format!("{}/oauth/authorize?", server.base_url())));
+		let redirect = format!("{}?code={}&state=xyz", listener.redirect_uri(), mock::AUTHORIZATION_CODE);
+		let browser = tokio::spawn(async move { reqwest::get(redirect).await.unwrap() });
+		let client = Client::oauth_with_listener_and_base_url(&server.base_url(), mock::API_KEY, provider, listener, "xyz").await.unwrap();
+		assert_eq!(client.tokens().access(), server.tokens().access());
+		browser.await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_mock_oauth() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let provider = Arc::new(Credentials::new(mock::CLIENT_ID, mock::CLIENT_SECRET));
+		let client = Client::oauth_with_base_url(&server.base_url(), mock::API_KEY, provider, mock::AUTHORIZATION_CODE, oauth::DEFAULT_REDIRECT_URI)
+			.await
+			.unwrap();
+		assert_eq!(client.tokens().access(), server.tokens().access());
+		assert!(client.tokens().expires_at().is_some());
+	}
+
+	#[tokio::test]
+	async fn test_mock_oauth_rotated_secret() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let provider = crate::credentials::Rotating::new([Credentials::new(mock::CLIENT_ID, "old-secret"), Credentials::new(mock::CLIENT_ID, mock::CLIENT_SECRET)]);
+		let client = Client::oauth_with_base_url(&server.base_url(), mock::API_KEY, provider, mock::AUTHORIZATION_CODE, oauth::DEFAULT_REDIRECT_URI)
+			.await
+			.unwrap();
+		assert_eq!(client.tokens().access(), server.tokens().access());
+		assert_eq!(server.token_requests(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_mock_oauth_unrotated_secret() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let provider = crate::credentials::Rotating::new([Credentials::new(mock::CLIENT_ID, "wrong-secret")]);
+		let result = Client::oauth_with_base_url(&server.base_url(), mock::API_KEY, provider, mock::AUTHORIZATION_CODE, oauth::DEFAULT_REDIRECT_URI).await;
+		assert!(matches!(result, Err(Error::Http(e)) if e.status() == Some(reqwest::StatusCode::UNAUTHORIZED)));
+		assert_eq!(server.token_requests(), 1);
+	}
+
+	#[tokio::test]
+	async fn test_mock_system_calls() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let client = server.client();
+		let systems = client.list_systems().await.unwrap();
+		assert_eq!(systems.len(), 2);
+		let system = systems.into_iter().find(|s| s.system_id == mock::SYSTEM_ID).unwrap();
+		assert_eq!(system.get_summary().await.unwrap().current_power, 3107);
+		assert_eq!(system.get_lifetime_production(None, None, false).await.unwrap().days.len(), 11);
+		assert_eq!(system.get_microinverter_production(&NaiveDate::from_ymd_opt(2023, 1, 11).unwrap(), None).await.unwrap().len(), 6);
+	}
+
+	#[tokio::test]
+	async fn test_mock_list_systems_pagination() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let client = server.client().with_page_size(1);
+		let page = client.list_systems_page(2).await.unwrap();
+		assert_eq!((page.total, page.current_page, page.size, page.count), (2, 2, 1, 1));
+		assert_eq!(page.items[0].system_id, 698905957);
+		let systems = client.list_systems().await.unwrap();
+		assert_eq!(systems.iter().map(|s| s.system_id).collect::<Vec<_>>(), [mock::SYSTEM_ID, 698905957]);
+	}
+
+	#[tokio::test]
+	async fn test_mock_page_size_clamped() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let client = server.client().with_page_size(0);
+		assert_eq!(client.session.page_size(), 1);
+		assert_eq!(client.list_systems().await.unwrap().len(), 2);
+		let client = client.with_page_size(MAX_PAGE_SIZE + 1);
+		assert_eq!(client.session.page_size(), MAX_PAGE_SIZE);
+	}
+
+	#[tokio::test]
+	async fn test_mock_find_system_by_envoy_serial() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let client = server.client();
+		let system = client.find_system_by_envoy_serial(mock::ENVOY_SERIAL).await.unwrap().unwrap();
+		assert_eq!(system.system_id, mock::SYSTEM_ID);
+		assert_eq!(system.name, "Mock Residence");
+		assert!(client.find_system_by_envoy_serial("000000000000").await.unwrap().is_none());
+		assert_eq!(client.get_system(698905957).await.unwrap().name, "Mock Cabin");
+	}
+
+	#[tokio::test]
+	async fn test_mock_settings() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let client = server.client();
+		let system = client.get_system(mock::SYSTEM_ID).await.unwrap();
+		assert_eq!(system.get_battery_settings().await.unwrap().reserve_percent, 30);
+		assert!(system.get_storm_guard().await.unwrap().enabled);
+		assert!(system.get_grid_status().await.unwrap().is_on_grid());
+		assert_eq!(system.get_load_control().await.unwrap().len(), 2);
+	}
+
+	#[cfg(feature = "control")]
+	#[tokio::test]
+	async fn test_mock_update_settings() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let client = server.client();
+		let system = client.get_system(mock::SYSTEM_ID).await.unwrap();
+		let update = BatterySettingsUpdate {
+			profile: Some(BatteryProfile::FullBackup),
+			reserve_percent: Some(100),
+			..Default::default()
+		};
+		let settings = system.set_battery_settings(&update).await.unwrap();
+		assert_eq!((settings.profile, settings.reserve_percent, settings.charge_from_grid), (BatteryProfile::FullBackup, 100, false));
+		assert_eq!(system.get_battery_settings().await.unwrap().profile, BatteryProfile::FullBackup);
+		assert!(!system.set_storm_guard(false).await.unwrap().enabled);
+		assert!(!system.get_storm_guard().await.unwrap().enabled);
+	}
+
+	#[tokio::test]
+	async fn test_mock_ev_charger() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let client = server.client().with_page_size(2);
+		let system = client.get_system(mock::SYSTEM_ID).await.unwrap();
+		let chargers = system.get_ev_chargers().await.unwrap();
+		let serial = &chargers[0].serial_number;
+		assert!(system.get_ev_charger_status(serial).await.unwrap().plugged_in);
+		let sessions = system.get_charging_sessions(serial).await.unwrap();
+		assert_eq!(sessions.len(), 3);
+		assert!(sessions[2].ended_at.is_none());
+		let lifetime = system.get_ev_charger_lifetime(serial, None, None).await.unwrap();
+		assert_eq!(lifetime.iter().map(|d| d.watt_hours).sum::<u32>(), 91904);
+	}
+
+	#[cfg(feature = "control")]
+	#[tokio::test]
+	async fn test_mock_ev_charger_control() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let client = server.client();
+		let system = client.get_system(mock::SYSTEM_ID).await.unwrap();
+		system.start_charging(mock::EV_CHARGER_SERIAL, Some(16)).await.unwrap();
+		let status = system.get_ev_charger_status(mock::EV_CHARGER_SERIAL).await.unwrap();
+		assert!(status.charging);
+		assert_eq!(status.charging_level, Some(16));
+		system.stop_charging(mock::EV_CHARGER_SERIAL).await.unwrap();
+		assert!(!system.get_ev_charger_status(mock::EV_CHARGER_SERIAL).await.unwrap().charging);
+	}
+
+	#[tokio::test]
+	async fn test_mock_events_and_alarms() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let client = server.client().with_page_size(3);
+		let system = client.list_systems().await.unwrap().into_iter().find(|s| s.system_id == mock::SYSTEM_ID).unwrap();
+		let start_time = DateTime::from_timestamp(1673136000, 0).unwrap();
+		let events = system.get_events(start_time, None).await.unwrap();
+		assert_eq!(events.len(), 4);
+		assert_eq!(system.get_events_page(start_time, Some(Utc::now()), 2).await.unwrap().items.len(), 1);
+		let alarms = system.get_alarms().await.unwrap();
+		assert_eq!(alarms.iter().filter(|a| a.is_open()).count(), 2);
+		let event_types = client.get_event_types().await.unwrap();
+		assert!(events.iter().all(|e| event_types.iter().any(|t| t.event_type_id == e.event_type_id)));
+	}
+
+	#[tokio::test]
+	async fn test_mock_rate_limited() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let client = server.client();
+		server.throttle_next(2);
+		assert!(matches!(client.list_systems().await, Err(Error::RateLimited(Some(retry_after))) if retry_after.as_secs() == 2));
+		client.list_systems().await.unwrap();
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_mock_governor_waits_out_retry_after() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let governor = Arc::new(Governor::new(Limits::WATT, Overflow::Queue));
+		let client = server.client().with_governor(governor.clone());
+		server.throttle_next(2);
+		client.list_systems().await.unwrap();
+		assert_eq!(governor.remaining().month_remaining, Limits::WATT.per_month - 2);
+	}
+
+	#[tokio::test]
+	async fn test_mock_retry_after_401() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let client = server.client();
+		let system = client.list_systems().await.unwrap().into_iter().find(|s| s.system_id == mock::SYSTEM_ID).unwrap();
+		server.expire_access_token();
+		system.get_summary().await.unwrap();
+		assert_eq!(server.token_requests(), 1);
+		server.expire_access_token();
+		client.list_systems().await.unwrap();
+		assert_eq!(server.token_requests(), 2);
+		assert_eq!(client.tokens().access(), server.tokens().access());
+	}
+
+	#[tokio::test]
+	async fn test_mock_concurrent_refresh() {
+		let server = mock::MockEnlighten::start().await.unwrap();
+		let client = server.client();
+		let (a, b, c) = tokio::join!(client.refresh(), client.refresh(), client.refresh());
+		assert_eq!(server.token_requests(), 1);
+		let (a, b, c) = (a.unwrap(), b.unwrap(), c.unwrap());
+		assert_eq!(a.access(), server.tokens().access());
+		assert_eq!(a, b);
+		assert_eq!(b, c);
+	}
 }
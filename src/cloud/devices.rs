@@ -0,0 +1,80 @@
+use chrono::DateTime;
+use chrono::Utc;
+use compact_str::CompactString;
+use serde::Deserialize;
+use serde_with::serde_as;
+use serde_with::TimestampSeconds;
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct DevicesResponse {
+	//system_id: u32,
+	//total_devices: u16,
+	//items: CompactString,
+	pub devices: Devices
+}
+
+/// Every device the cloud knows about for a system, grouped by type.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Devices {
+	#[serde(default)]
+	pub micros: Vec<Device>,
+	#[serde(default)]
+	pub meters: Vec<Meter>,
+	#[serde(default)]
+	pub gateways: Vec<Gateway>,
+	#[serde(default)]
+	pub encharges: Vec<Device>,
+	#[serde(default)]
+	pub enpowers: Vec<Device>
+}
+
+/// Serial numbers match [`crate::envoy::Device::serial_num`] for devices also reported locally.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct Device {
+	pub id: u64,
+	pub serial_number: CompactString,
+	pub name: CompactString,
+	pub part_number: Option<CompactString>,
+	pub sku: Option<CompactString>,
+	pub model: Option<CompactString>,
+	pub product_name: Option<CompactString>,
+	pub status: CompactString,
+	pub active: bool,
+	/// `None` if the device has never reported.
+	#[serde_as(as = "Option<TimestampSeconds<i64>>")]
+	pub last_report_at: Option<DateTime<Utc>>
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Meter {
+	#[serde(flatten)]
+	pub device: Device,
+	pub state: Option<CompactString>,
+	pub config_type: Option<CompactString>
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Gateway {
+	#[serde(flatten)]
+	pub device: Device,
+	pub emu_sw_version: Option<CompactString>
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_deserialize_devices() {
+		let response: DevicesResponse = serde_json::from_str(include_str!("mock/testdata/devices.json")).unwrap();
+		let devices = response.devices;
+		assert_eq!(devices.micros.len(), 2);
+		assert_eq!(devices.micros[1].status, "micro");
+		assert_eq!(devices.meters[0].config_type.as_deref(), Some("Net"));
+		assert_eq!(devices.meters[0].device.sku, None);
+		assert_eq!(devices.gateways[0].emu_sw_version.as_deref(), Some("D7.0.88"));
+		assert_eq!(devices.encharges[1].last_report_at, None);
+		assert_eq!(devices.enpowers.len(), 1);
+	}
+}
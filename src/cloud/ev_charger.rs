@@ -0,0 +1,93 @@
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+use compact_str::CompactString;
+use serde::Deserialize;
+#[cfg(feature = "control")] use serde::Serialize;
+use serde_with::serde_as;
+use serde_with::TimestampSeconds;
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct EvChargersResponse {
+	//system_id: u32,
+	//total_devices: u16,
+	//items: CompactString,
+	pub devices: Vec<EvCharger>
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct EvCharger {
+	pub serial_number: CompactString,
+	pub name: CompactString,
+	pub model: Option<CompactString>,
+	pub part_number: Option<CompactString>,
+	pub status: CompactString,
+	pub active: bool,
+	#[serde_as(as = "Option<TimestampSeconds<i64>>")]
+	pub last_report_at: Option<DateTime<Utc>>
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct EvChargerStatus {
+	/// E.g. `AVAILABLE`, `PLUGGED_IN` or `CHARGING`.
+	pub status: CompactString,
+	pub plugged_in: bool,
+	pub charging: bool,
+	#[serde(rename = "power")]
+	pub power_watts: u32,
+	/// Energy delivered so far in the current session.
+	pub session_energy_wh: u32,
+	/// Charging current limit, in amps.
+	pub charging_level: Option<u8>,
+	#[serde_as(as = "TimestampSeconds<i64>")]
+	pub last_report_at: DateTime<Utc>
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct EvChargerLifetimeResponse {
+	//system_id: u32,
+	//serial_number: CompactString,
+	pub start_date: NaiveDate,
+	pub consumption: Vec<u32>
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct ChargingSession {
+	#[serde_as(as = "TimestampSeconds<i64>")]
+	#[serde(rename = "start_time")]
+	pub started_at: DateTime<Utc>,
+	/// `None` while the session is in progress.
+	#[serde_as(as = "Option<TimestampSeconds<i64>>")]
+	#[serde(rename = "end_time")]
+	pub ended_at: Option<DateTime<Utc>>,
+	pub energy_added_kwh: f32,
+	pub miles_added: Option<f32>,
+	/// In the site's currency, if a tariff is configured.
+	pub cost: Option<f32>
+}
+
+#[cfg(feature = "control")]
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StartChargingRequest {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub charging_level: Option<u8>,
+	pub connector_id: u8
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::cloud::Page;
+
+	#[test]
+	fn test_deserialize_sessions() {
+		let page: Page<ChargingSession> = serde_json::from_str(include_str!("mock/testdata/ev_charger_sessions.json")).unwrap();
+		assert_eq!(page.items.len(), 3);
+		assert_eq!(page.items[0].cost, Some(3.12));
+		assert_eq!(page.items[2].ended_at, None);
+	}
+}
@@ -0,0 +1,83 @@
+use chrono::DateTime;
+use chrono::Utc;
+use compact_str::CompactString;
+use serde::Deserialize;
+use serde_with::serde_as;
+use serde_with::TimestampSeconds;
+
+/// Something that happened on a system, e.g. a device dropping off or a grid outage.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct Event {
+	/// See [`super::Client::get_event_types`].
+	pub event_type_id: u32,
+	#[serde(rename = "event_description")]
+	pub description: CompactString,
+	/// The device the event concerns, if any.
+	pub serial_number: Option<CompactString>,
+	pub device_type: Option<CompactString>,
+	#[serde_as(as = "TimestampSeconds<i64>")]
+	pub start_at: DateTime<Utc>,
+	#[serde_as(as = "Option<TimestampSeconds<i64>>")]
+	#[serde(default)]
+	pub end_at: Option<DateTime<Utc>>
+}
+
+/// An event that needs attention.  Alarms stay open until the condition clears.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct Alarm {
+	pub id: u64,
+	pub event_type_id: u32,
+	pub description: CompactString,
+	pub severity: CompactString,
+	pub serial_number: Option<CompactString>,
+	#[serde_as(as = "TimestampSeconds<i64>")]
+	pub raised_at: DateTime<Utc>,
+	#[serde_as(as = "Option<TimestampSeconds<i64>>")]
+	#[serde(default)]
+	pub cleared_at: Option<DateTime<Utc>>
+}
+
+impl Alarm {
+	#[inline]
+	pub fn is_open(&self) -> bool {
+		self.cleared_at.is_none()
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EventType {
+	pub event_type_id: u32,
+	#[serde(rename = "event_type")]
+	pub name: CompactString,
+	#[serde(rename = "event_description")]
+	pub description: CompactString,
+	pub recommended_action: Option<CompactString>
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct EventTypesResponse {
+	pub event_types: Vec<EventType>
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::cloud::Page;
+
+	#[test]
+	fn test_deserialize_alarms() {
+		let page: Page<Alarm> = serde_json::from_str(include_str!("mock/testdata/alarms.json")).unwrap();
+		assert_eq!(page.total, 3);
+		assert_eq!(page.items.iter().filter(|a| a.is_open()).count(), 2);
+		assert_eq!(page.items[0].serial_number.as_deref(), Some("121935144672"));
+	}
+
+	#[test]
+	fn test_deserialize_event_types() {
+		let response: EventTypesResponse = serde_json::from_str(include_str!("mock/testdata/event_types.json")).unwrap();
+		assert_eq!(response.event_types.len(), 4);
+		assert_eq!(response.event_types[3].recommended_action, None);
+	}
+}
@@ -0,0 +1,345 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::DateTime;
+use chrono::Datelike;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::time::Instant;
+
+use super::token_store::write_atomically;
+use super::Error;
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// Request limits of an Enlighten API plan.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Limits {
+	pub per_minute: u32,
+	pub per_month: u32
+}
+
+impl Limits {
+	/// The free Watt plan.
+	pub const WATT: Self = Self::new(10, 1000);
+
+	#[inline]
+	pub const fn new(per_minute: u32, per_month: u32) -> Self {
+		Self { per_minute, per_month }
+	}
+}
+
+/// What to do with a request that would exceed the per-minute limit.  Requests that would
+/// exceed the monthly limit are always rejected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Overflow {
+	/// Wait until the request fits within the limit.
+	Queue,
+	/// Fail immediately with [`Error::RateLimited`].
+	Reject
+}
+
+/// How many requests have been made in a calendar month (UTC).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MonthlyUsage {
+	pub year: i32,
+	pub month: u32,
+	pub count: u32
+}
+
+impl MonthlyUsage {
+	#[inline]
+	fn current(now: DateTime<Utc>) -> Self {
+		Self { year: now.year(), month: now.month(), count: 0 }
+	}
+
+	#[inline]
+	fn is_current(&self, now: DateTime<Utc>) -> bool {
+		self.year == now.year() && self.month == now.month()
+	}
+}
+
+/// Persists the monthly request count across restarts.
+pub trait QuotaStore: Send + Sync {
+	fn load(&self) -> io::Result<Option<MonthlyUsage>>;
+	fn save(&self, usage: &MonthlyUsage) -> io::Result<()>;
+}
+
+/// Stores the monthly usage as JSON, written atomically the same way as
+/// [`super::FileTokenStore`].
+#[derive(Clone, Debug)]
+pub struct FileQuotaStore {
+	path: PathBuf
+}
+
+impl FileQuotaStore {
+	#[inline]
+	pub fn new(path: impl Into<PathBuf>) -> Self {
+		Self { path: path.into() }
+	}
+}
+
+impl QuotaStore for FileQuotaStore {
+	fn load(&self) -> io::Result<Option<MonthlyUsage>> {
+		match std::fs::read(&self.path) {
+			Ok(data) => Ok(Some(serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?)),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+			Err(e) => Err(e)
+		}
+	}
+
+	#[inline]
+	fn save(&self, usage: &MonthlyUsage) -> io::Result<()> {
+		write_atomically(&self.path, &serde_json::to_vec(usage)?)
+	}
+}
+
+/// Remaining request quota, as far as the [`Governor`] knows.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Quota {
+	pub minute_remaining: u32,
+	pub month_remaining: u32,
+	/// When the monthly count resets.
+	pub month_resets_at: DateTime<Utc>,
+	/// How long the API has asked us to back off for, if it has.
+	pub retry_after: Option<Duration>
+}
+
+#[derive(Debug)]
+struct State {
+	recent: VecDeque<Instant>,
+	usage: MonthlyUsage,
+	blocked_until: Option<Instant>
+}
+
+impl State {
+	fn prune(&mut self, now: Instant) {
+		while (self.recent.front().is_some_and(|t| now.duration_since(*t) >= MINUTE)) {
+			self.recent.pop_front();
+		}
+		if (self.blocked_until.is_some_and(|t| t <= now)) {
+			self.blocked_until = None;
+		}
+	}
+
+	/// How long until a request may be sent, if it can't be sent now.
+	fn wait(&self, limits: &Limits, now: Instant) -> Option<Duration> {
+		let minute = match self.recent.len() >= limits.per_minute as usize {
+			true => self.recent.front().map(|t| MINUTE.saturating_sub(now.duration_since(*t))),
+			false => None
+		};
+		let blocked = self.blocked_until.map(|t| t.duration_since(now));
+		minute.max(blocked)
+	}
+}
+
+/// Keeps a [`super::Client`] within its plan's per-minute and monthly request limits.  One
+/// governor should be shared by every client using the same API key.
+pub struct Governor {
+	limits: Limits,
+	overflow: Overflow,
+	state: Mutex<State>,
+	store: Option<Arc<dyn QuotaStore>>,
+	saving: tokio::sync::Mutex<()>
+}
+
+impl Governor {
+	#[inline]
+	pub fn new(limits: Limits, overflow: Overflow) -> Self {
+		Self {
+			limits,
+			overflow,
+			state: Mutex::new(State {
+				recent: VecDeque::new(),
+				usage: MonthlyUsage::current(Utc::now()),
+				blocked_until: None
+			}),
+			store: None,
+			saving: tokio::sync::Mutex::default()
+		}
+	}
+
+	/// Loads this month's count from `store`, and saves the count to it after every request.
+	pub fn with_store(mut self, store: Arc<dyn QuotaStore>) -> io::Result<Self> {
+		if let Some(usage) = store.load()? {
+			if (usage.is_current(Utc::now())) {
+				self.state.get_mut().unwrap().usage = usage;
+			}
+		}
+		self.store = Some(store);
+		Ok(self)
+	}
+
+	#[inline]
+	pub fn limits(&self) -> Limits {
+		self.limits
+	}
+
+	pub fn remaining(&self) -> Quota {
+		let now = Instant::now();
+		let utc_now = Utc::now();
+		let mut state = self.state.lock().unwrap();
+		state.prune(now);
+		let month_used = match state.usage.is_current(utc_now) {
+			true => state.usage.count,
+			false => 0
+		};
+		let next_month = match utc_now.month() {
+			12 => NaiveDate::from_ymd_opt(utc_now.year() + 1, 1, 1),
+			month => NaiveDate::from_ymd_opt(utc_now.year(), month + 1, 1)
+		};
+		Quota {
+			minute_remaining: self.limits.per_minute.saturating_sub(state.recent.len() as u32),
+			month_remaining: self.limits.per_month.saturating_sub(month_used),
+			// The first of any month exists.
+			month_resets_at: Utc.from_utc_datetime(&next_month.unwrap().and_hms_opt(0, 0, 0).unwrap()),
+			retry_after: state.blocked_until.map(|t| t.duration_since(now))
+		}
+	}
+
+	/// Waits for (or, in [`Overflow::Reject`] mode, checks for) room to send one request, then
+	/// counts it.
+	pub(crate) async fn acquire(&self) -> Result<(), Error> {
+		loop {
+			let wait = {
+				let now = Instant::now();
+				let utc_now = Utc::now();
+				let mut state = self.state.lock().unwrap();
+				state.prune(now);
+				if (!state.usage.is_current(utc_now)) {
+					state.usage = MonthlyUsage::current(utc_now);
+				}
+				if (state.usage.count >= self.limits.per_month) {
+					return Err(Error::QuotaExhausted);
+				}
+				let wait = state.wait(&self.limits, now);
+				if (wait.is_none()) {
+					state.recent.push_back(now);
+					state.usage.count += 1;
+				}
+				wait
+			};
+			let Some(wait) = wait else {
+				if let Some(store) = &self.store {
+					self.save(store.clone()).await?;
+				}
+				return Ok(());
+			};
+			match self.overflow {
+				Overflow::Queue => tokio::time::sleep(wait).await,
+				Overflow::Reject => return Err(Error::RateLimited(Some(wait)))
+			}
+		}
+	}
+
+	/// Saves the count off the runtime.  Saves are serialized and each writes the latest count, so
+	/// concurrent requests can't leave an older count in the store.
+	async fn save(&self, store: Arc<dyn QuotaStore>) -> Result<(), Error> {
+		let _saving = self.saving.lock().await;
+		let usage = self.state.lock().unwrap().usage;
+		crate::unblock(move || store.save(&usage)).await.map_err(Error::QuotaStore)
+	}
+
+	/// Records that the API responded with 429, asking us to wait `retry_after`.
+	pub(crate) fn throttled(&self, retry_after: Duration) {
+		let until = Instant::now() + retry_after;
+		let mut state = self.state.lock().unwrap();
+		state.blocked_until = state.blocked_until.max(Some(until));
+	}
+
+	#[inline]
+	pub(crate) fn overflow(&self) -> Overflow {
+		self.overflow
+	}
+}
+
+impl fmt::Debug for Governor {
+	#[inline]
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Governor")
+			.field("limits", &self.limits)
+			.field("overflow", &self.overflow)
+			.field("state", &self.state)
+			.finish_non_exhaustive()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+
+	use super::*;
+
+	#[tokio::test(start_paused = true)]
+	async fn test_reject_per_minute() {
+		let governor = Governor::new(Limits::new(2, 100), Overflow::Reject);
+		governor.acquire().await.unwrap();
+		governor.acquire().await.unwrap();
+		assert!(matches!(governor.acquire().await, Err(Error::RateLimited(Some(wait))) if wait == MINUTE));
+		assert_eq!(governor.remaining().minute_remaining, 0);
+		assert_eq!(governor.remaining().month_remaining, 98);
+		tokio::time::advance(MINUTE).await;
+		governor.acquire().await.unwrap();
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_queue_per_minute() {
+		let governor = Governor::new(Limits::new(2, 100), Overflow::Queue);
+		let start = Instant::now();
+		for _ in 0..3 {
+			governor.acquire().await.unwrap();
+		}
+		assert_eq!(start.elapsed(), MINUTE);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_monthly_quota() {
+		let governor = Governor::new(Limits::new(10, 2), Overflow::Queue);
+		governor.acquire().await.unwrap();
+		governor.acquire().await.unwrap();
+		assert!(matches!(governor.acquire().await, Err(Error::QuotaExhausted)));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_throttled() {
+		let governor = Governor::new(Limits::WATT, Overflow::Queue);
+		governor.throttled(Duration::from_secs(5));
+		assert_eq!(governor.remaining().retry_after, Some(Duration::from_secs(5)));
+		let start = Instant::now();
+		governor.acquire().await.unwrap();
+		assert_eq!(start.elapsed(), Duration::from_secs(5));
+	}
+
+	#[tokio::test]
+	async fn test_file_quota_store() {
+		let path = env::temp_dir().join(format!("enphase-test-quota-store-{}.json", std::process::id()));
+		let store = Arc::new(FileQuotaStore::new(&path));
+		let governor = Governor::new(Limits::WATT, Overflow::Reject).with_store(store.clone()).unwrap();
+		governor.acquire().await.unwrap();
+		governor.acquire().await.unwrap();
+		let governor = Governor::new(Limits::WATT, Overflow::Reject).with_store(store).unwrap();
+		assert_eq!(governor.remaining().month_remaining, 998);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_file_quota_store_concurrent() {
+		let path = env::temp_dir().join(format!("enphase-test-quota-store-concurrent-{}.json", std::process::id()));
+		let store = Arc::new(FileQuotaStore::new(&path));
+		let governor = Governor::new(Limits::WATT, Overflow::Reject).with_store(store.clone()).unwrap();
+		let (a, b, c) = tokio::join!(governor.acquire(), governor.acquire(), governor.acquire());
+		a.unwrap();
+		b.unwrap();
+		c.unwrap();
+		assert_eq!(store.load().unwrap().unwrap().count, 3);
+		std::fs::remove_file(&path).unwrap();
+	}
+}
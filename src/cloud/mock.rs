@@ -0,0 +1,321 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+
+use base64::engine::Engine;
+use hyper::body::to_bytes;
+use hyper::header;
+use hyper::Body;
+use hyper::Method;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
+
+use crate::mock::response;
+use crate::mock::MockServer;
+use super::Client;
+use super::System;
+use super::Tokens;
+
+pub const API_KEY: &str = "mock-api-key";
+pub const CLIENT_ID: &str = "mock-client-id";
+pub const CLIENT_SECRET: &str = "mock-client-secret";
+/// The only authorization code the token endpoint accepts.
+pub const AUTHORIZATION_CODE: &str = "mock-authorization-code";
+/// The system the bundled fixtures describe.
+pub const SYSTEM_ID: u32 = 698905956;
+/// The serial number of [`SYSTEM_ID`]'s Envoy, matching the Envoy mock's `info.xml`.
+pub const ENVOY_SERIAL: &str = "121915008901";
+/// The serial number of [`SYSTEM_ID`]'s IQ EV charger.
+pub const EV_CHARGER_SERIAL: &str = "202211180001";
+
+const FIXTURES: &[(&str, &str)] = &[
+	("/api/v4/systems", include_str!("mock/testdata/systems.json")),
+	("/api/v4/systems/event_types", include_str!("mock/testdata/event_types.json")),
+	("/api/v4/systems/config/698905956/battery_settings", include_str!("mock/testdata/battery_settings.json")),
+	("/api/v4/systems/config/698905956/grid_status", include_str!("mock/testdata/grid_status.json")),
+	("/api/v4/systems/config/698905956/load_control", include_str!("mock/testdata/load_control.json")),
+	("/api/v4/systems/config/698905956/storm_guard", include_str!("mock/testdata/storm_guard.json")),
+	("/api/v4/systems/698905956/alarms", include_str!("mock/testdata/alarms.json")),
+	("/api/v4/systems/698905956/summary", include_str!("mock/testdata/summary.json")),
+	("/api/v4/systems/698905956/consumption_lifetime", include_str!("mock/testdata/consumption_lifetime.json")),
+	("/api/v4/systems/698905956/devices", include_str!("mock/testdata/devices.json")),
+	("/api/v4/systems/698905956/devices/micros/121935144671/telemetry", include_str!("mock/testdata/micro_telemetry.json")),
+	("/api/v4/systems/698905956/energy_export_telemetry", include_str!("mock/testdata/energy_export_telemetry.json")),
+	("/api/v4/systems/698905956/energy_import_telemetry", include_str!("mock/testdata/energy_import_telemetry.json")),
+	("/api/v4/systems/698905956/energy_lifetime", include_str!("mock/testdata/energy_lifetime.json")),
+	("/api/v4/systems/698905956/ev_charger/devices", include_str!("mock/testdata/ev_charger_devices.json")),
+	("/api/v4/systems/698905956/ev_charger/202211180001/lifetime", include_str!("mock/testdata/ev_charger_lifetime.json")),
+	("/api/v4/systems/698905956/ev_charger/202211180001/sessions", include_str!("mock/testdata/ev_charger_sessions.json")),
+	("/api/v4/systems/698905956/ev_charger/202211180001/status", include_str!("mock/testdata/ev_charger_status.json")),
+	("/api/v4/systems/698905956/events", include_str!("mock/testdata/events.json")),
+	("/api/v4/systems/698905956/latest_telemetry", include_str!("mock/testdata/latest_telemetry.json")),
+	("/api/v4/systems/698905956/telemetry/battery", include_str!("mock/testdata/battery.json")),
+	("/api/v4/systems/698905956/telemetry/consumption_meter", include_str!("mock/testdata/consumption_meter.json")),
+	("/api/v4/systems/698905956/telemetry/production_meter", include_str!("mock/testdata/production_meter.json")),
+	("/api/v4/systems/698905956/telemetry/production_micro", include_str!("mock/testdata/production_micro.json"))
+];
+
+#[derive(Debug, Default)]
+struct TokenState {
+	generation: u32,
+	access_expired: bool
+}
+
+impl TokenState {
+	#[inline]
+	fn tokens(&self) -> Tokens {
+		Tokens::new(format!("mock-access-{}", self.generation), format!("mock-refresh-{}", self.generation), None)
+	}
+}
+
+#[derive(Debug)]
+struct State {
+	fixtures: RwLock<BTreeMap<String, String>>,
+	tokens: Mutex<TokenState>,
+	token_requests: AtomicUsize,
+	throttled: Mutex<Option<u64>>
+}
+
+/// An in-process fake of the Enlighten v4 API, listening on an ephemeral localhost port.  It
+/// implements the OAuth token endpoint (rotating tokens on every exchange) and serves fixture
+/// bodies for everything under `/api/v4`, requiring the API key and a current access token.
+/// The server shuts down when this is dropped.
+#[derive(Debug)]
+pub struct MockEnlighten {
+	server: MockServer,
+	state: Arc<State>
+}
+
+impl MockEnlighten {
+	/// Starts the server on the current Tokio runtime, loaded with the bundled fixtures.
+	pub async fn start() -> io::Result<Self> {
+		let state = Arc::new(State {
+			fixtures: RwLock::new(FIXTURES.iter().map(|(path, body)| (path.to_string(), body.to_string())).collect()),
+			tokens: Mutex::default(),
+			token_requests: AtomicUsize::new(0),
+			throttled: Mutex::default()
+		});
+		let server = MockServer::start(state.clone(), handle)?;
+		Ok(Self { server, state })
+	}
+
+	#[inline]
+	pub fn base_url(&self) -> String {
+		format!("http://{}", self.server.addr())
+	}
+
+	/// Serves `body` for GETs of `path` (e.g. `/api/v4/systems/1/summary`), replacing any
+	/// bundled fixture.
+	pub fn set_fixture(&self, path: impl Into<String>, body: impl Into<String>) {
+		self.state.fixtures.write().unwrap().insert(path.into(), body.into());
+	}
+
+	/// The tokens the server currently accepts.
+	pub fn tokens(&self) -> Tokens {
+		self.state.tokens.lock().unwrap().tokens()
+	}
+
+	/// Rejects the current access token with a 401 until the client refreshes.
+	pub fn expire_access_token(&self) {
+		self.state.tokens.lock().unwrap().access_expired = true;
+	}
+
+	/// Answers the next API request with 429 and `Retry-After: {retry_after}`.
+	pub fn throttle_next(&self, retry_after: u64) {
+		*self.state.throttled.lock().unwrap() = Some(retry_after);
+	}
+
+	/// How many requests the token endpoint has received, successful or not.
+	pub fn token_requests(&self) -> usize {
+		self.state.token_requests.load(Ordering::SeqCst)
+	}
+
+	/// A client already holding the server's current tokens.
+	pub fn client(&self) -> Client {
+		let tokens = self.tokens();
+		Client::preauth(API_KEY, CLIENT_ID.into(), CLIENT_SECRET.into(), tokens.access, tokens.refresh).with_base_url(&self.base_url())
+	}
+
+	/// [`SYSTEM_ID`], fetched through [`MockEnlighten::client`].
+	pub async fn system(&self) -> System {
+		// The systems fixture always includes it.
+		self.client().get_system(SYSTEM_ID).await.unwrap()
+	}
+}
+
+#[inline]
+fn error(status: StatusCode, message: &str) -> Response<Body> {
+	response(status, "application/json", serde_json::json!({ "message": message }).to_string())
+}
+
+/// The array each paginated endpoint pages.
+fn paginated_field(path: &str) -> Option<&'static str> {
+	match path {
+		"/api/v4/systems" => Some("systems"),
+		path if path.ends_with("/events") => Some("events"),
+		path if path.ends_with("/alarms") => Some("alarms"),
+		path if path.ends_with("/sessions") => Some("sessions"),
+		_ => None
+	}
+}
+
+/// Serves the requested page of a fixture, the way the real endpoints page their arrays.
+fn paginate(body: &str, field: &str, query: &BTreeMap<String, String>) -> String {
+	let Ok(serde_json::Value::Object(mut object)) = serde_json::from_str(body) else {
+		return body.to_owned();
+	};
+	let Some(serde_json::Value::Array(items)) = object.remove(field) else {
+		return body.to_owned();
+	};
+	let page: usize = query.get("page").and_then(|p| p.parse().ok()).unwrap_or(1).max(1);
+	let size: usize = query.get("size").and_then(|s| s.parse().ok()).unwrap_or(10).max(1);
+	let total = items.len();
+	let items: Vec<_> = items.into_iter().skip((page - 1) * size).take(size).collect();
+	object.insert("total".into(), total.into());
+	object.insert("current_page".into(), page.into());
+	object.insert("size".into(), size.into());
+	object.insert("count".into(), items.len().into());
+	object.insert(field.into(), items.into());
+	serde_json::Value::Object(object).to_string()
+}
+
+/// Serves one system out of the systems fixture.
+fn find_system(body: &str, system_id: u64) -> Option<String> {
+	let serde_json::Value::Object(mut object) = serde_json::from_str(body).ok()? else {
+		return None;
+	};
+	let serde_json::Value::Array(systems) = object.remove("systems")? else {
+		return None;
+	};
+	systems.into_iter().find(|s| s["system_id"].as_u64() == Some(system_id)).map(|s| s.to_string())
+}
+
+/// Checks an API request's key and access token, and applies any pending throttle.
+fn reject(state: &State, query: &BTreeMap<String, String>, authorization: &str) -> Option<Response<Body>> {
+	if (query.get("key").map(String::as_str) != Some(API_KEY)) {
+		return Some(error(StatusCode::UNAUTHORIZED, "Invalid API key"));
+	}
+	let accepted = {
+		let tokens = state.tokens.lock().unwrap();
+		!tokens.access_expired && authorization == format!("Bearer {}", tokens.tokens().access)
+	};
+	if (!accepted) {
+		return Some(error(StatusCode::UNAUTHORIZED, "Invalid or expired access token"));
+	}
+	let retry_after = state.throttled.lock().unwrap().take()?;
+	let mut response = error(StatusCode::TOO_MANY_REQUESTS, "Too many requests");
+	response.headers_mut().insert(header::RETRY_AFTER, retry_after.into());
+	Some(response)
+}
+
+async fn handle(state: Arc<State>, request: Request<Body>) -> Response<Body> {
+	let (parts, body) = request.into_parts();
+	let query: BTreeMap<String, String> = url::form_urlencoded::parse(parts.uri.query().unwrap_or_default().as_bytes()).into_owned().collect();
+	let authorization = parts.headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()).unwrap_or_default();
+
+	match (&parts.method, parts.uri.path()) {
+		(&Method::POST, "/oauth/token") => {
+			state.token_requests.fetch_add(1, Ordering::SeqCst);
+			let expected = format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("{CLIENT_ID}:{CLIENT_SECRET}")));
+			if (authorization != expected) {
+				return error(StatusCode::UNAUTHORIZED, "Invalid client credentials");
+			}
+			let mut tokens = state.tokens.lock().unwrap();
+			let valid = match query.get("grant_type").map(String::as_str) {
+				Some("authorization_code") => query.get("code").is_some_and(|code| code == AUTHORIZATION_CODE),
+				Some("refresh_token") => query.get("refresh_token").is_some_and(|refresh| *refresh == tokens.tokens().refresh),
+				_ => false
+			};
+			if (!valid) {
+				return error(StatusCode::BAD_REQUEST, "Invalid grant");
+			}
+			tokens.generation += 1;
+			tokens.access_expired = false;
+			let issued = tokens.tokens();
+			let body = serde_json::json!({
+				"access_token": issued.access,
+				"token_type": "bearer",
+				"refresh_token": issued.refresh,
+				"expires_in": 86399,
+				"scope": "read write",
+				"enl_uid": "1234567",
+				"enl_cid": "1234",
+				"is_internal_app": false,
+				"app_type": "partner"
+			});
+			response(StatusCode::OK, "application/json", body.to_string())
+		},
+		(&Method::GET, path) if path.starts_with("/api/v4/") => {
+			if let Some(rejection) = reject(&state, &query, authorization) {
+				return rejection;
+			}
+			if (path == "/api/v4/systems/retrieve_system_id") {
+				return match query.get("serial_num").map(String::as_str) {
+					Some(ENVOY_SERIAL) => response(StatusCode::OK, "application/json", serde_json::json!({ "system_id": SYSTEM_ID }).to_string()),
+					_ => error(StatusCode::NOT_FOUND, "No system found for serial number")
+				};
+			}
+			let fixtures = state.fixtures.read().unwrap();
+			if let Some(system_id) = path.strip_prefix("/api/v4/systems/").and_then(|id| id.parse().ok()) {
+				return match fixtures.get("/api/v4/systems").and_then(|body| find_system(body, system_id)) {
+					Some(body) => response(StatusCode::OK, "application/json", body),
+					None => error(StatusCode::NOT_FOUND, "Not found")
+				};
+			}
+			match (fixtures.get(path), paginated_field(path)) {
+				(Some(body), Some(field)) => response(StatusCode::OK, "application/json", paginate(body, field, &query)),
+				(Some(body), None) => response(StatusCode::OK, "application/json", body.clone()),
+				(None, _) => error(StatusCode::NOT_FOUND, "Not found")
+			}
+		},
+		// Charger commands update the charger's status fixture.
+		(&Method::POST, path) if path.starts_with("/api/v4/") => {
+			if let Some(rejection) = reject(&state, &query, authorization) {
+				return rejection;
+			}
+			let Some((charger, command)) = path.rsplit_once('/') else {
+				return error(StatusCode::NOT_FOUND, "Not found");
+			};
+			let request: serde_json::Value = to_bytes(body).await.ok().and_then(|body| serde_json::from_slice(&body).ok()).unwrap_or_default();
+			let update = match command {
+				"start_charging" => serde_json::json!({ "status": "CHARGING", "charging": true, "charging_level": request["chargingLevel"] }),
+				"stop_charging" => serde_json::json!({ "status": "PLUGGED_IN", "charging": false, "power": 0 }),
+				_ => return error(StatusCode::NOT_FOUND, "Not found")
+			};
+			let status_path = format!("{charger}/status");
+			let mut fixtures = state.fixtures.write().unwrap();
+			let Some(Ok(serde_json::Value::Object(mut status))) = fixtures.get(&status_path).map(|body| serde_json::from_str(body)) else {
+				return error(StatusCode::NOT_FOUND, "Not found");
+			};
+			if let serde_json::Value::Object(update) = update {
+				status.extend(update.into_iter().filter(|(_, v)| !v.is_null()));
+			}
+			fixtures.insert(status_path, serde_json::Value::Object(status).to_string());
+			response(StatusCode::OK, "application/json", serde_json::json!({ "message": "Request sent successfully" }).to_string())
+		},
+		// Settings updates: the body's fields replace those of the fixture.
+		(&Method::PUT, path) if path.starts_with("/api/v4/") => {
+			if let Some(rejection) = reject(&state, &query, authorization) {
+				return rejection;
+			}
+			let update = match to_bytes(body).await.ok().and_then(|body| serde_json::from_slice(&body).ok()) {
+				Some(serde_json::Value::Object(update)) => update,
+				_ => return error(StatusCode::BAD_REQUEST, "Invalid body")
+			};
+			let mut fixtures = state.fixtures.write().unwrap();
+			let Some(Ok(serde_json::Value::Object(mut object))) = fixtures.get(path).map(|body| serde_json::from_str(body)) else {
+				return error(StatusCode::NOT_FOUND, "Not found");
+			};
+			object.extend(update);
+			let body = serde_json::Value::Object(object).to_string();
+			fixtures.insert(path.to_owned(), body.clone());
+			response(StatusCode::OK, "application/json", body)
+		},
+		_ => error(StatusCode::NOT_FOUND, "Not found")
+	}
+}
@@ -0,0 +1,189 @@
+#[cfg(feature = "oauth-listener")] use std::io;
+#[cfg(feature = "oauth-listener")] use std::net::SocketAddr;
+#[cfg(feature = "oauth-listener")] use std::sync::Arc;
+
+#[cfg(feature = "oauth-listener")] use tokio::io::AsyncReadExt;
+#[cfg(feature = "oauth-listener")] use tokio::io::AsyncWriteExt;
+#[cfg(feature = "oauth-listener")] use tokio::net::TcpListener;
+#[cfg(feature = "oauth-listener")] use tokio::net::TcpStream;
+#[cfg(feature = "oauth-listener")] use tokio::net::ToSocketAddrs;
+#[cfg(feature = "oauth-listener")] use tokio::task::JoinSet;
+use url::Url;
+
+/// The redirect URI Enlighten assigns to applications that don't register their own; it displays
+/// the authorization code for the user to copy.
+pub const DEFAULT_REDIRECT_URI: &str = "https://api.enphaseenergy.com/oauth/redirect_uri";
+
+/// Builds the URL to send the user's browser to.  After logging in, Enlighten redirects to
+/// `redirect_uri` with `code` and `state` query parameters.
+pub fn authorize_url(client_id: &str, redirect_uri: &str, state: &str) -> Url {
+	// The default base URL is a constant and always parses.
+	authorize_url_with_base_url(super::DEFAULT_BASE_URL, client_id, redirect_uri, state).unwrap()
+}
+
+/// Like [`authorize_url`], but for the API at `base_url` instead of [`super::DEFAULT_BASE_URL`].
+pub fn authorize_url_with_base_url(base_url: &str, client_id: &str, redirect_uri: &str, state: &str) -> Result<Url, url::ParseError> {
+	Url::parse_with_params(
+		&format!("{}/oauth/authorize", base_url.trim_end_matches('/')),
+		&[("response_type", "code"), ("client_id", client_id), ("redirect_uri", redirect_uri), ("state", state)]
+	)
+}
+
+#[cfg(feature = "oauth-listener")]
+#[derive(Debug, thiserror::Error)]
+pub enum ListenerError {
+	#[error("I/O error: {0}")]
+	Io(#[from] io::Error),
+	#[error("Authorization was denied: {0}")]
+	Denied(String)
+}
+
+/// A minimal HTTP listener on localhost that receives the OAuth redirect.  Register
+/// [`RedirectListener::redirect_uri`] with your Enlighten application.
+#[cfg(feature = "oauth-listener")]
+#[derive(Debug)]
+pub struct RedirectListener {
+	listener: TcpListener,
+	redirect_uri: String
+}
+
+#[cfg(feature = "oauth-listener")]
+impl RedirectListener {
+	pub async fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+		let listener = TcpListener::bind(addr).await?;
+		let redirect_uri = match listener.local_addr()? {
+			SocketAddr::V4(addr) => format!("http://{addr}/"),
+			SocketAddr::V6(addr) => format!("http://[{}]:{}/", addr.ip(), addr.port())
+		};
+		Ok(Self { listener, redirect_uri })
+	}
+
+	#[inline]
+	pub fn redirect_uri(&self) -> &str {
+		&self.redirect_uri
+	}
+
+	#[inline]
+	pub fn authorize_url(&self, client_id: &str, state: &str) -> Url {
+		authorize_url(client_id, &self.redirect_uri, state)
+	}
+
+	#[inline]
+	pub fn authorize_url_with_base_url(&self, base_url: &str, client_id: &str, state: &str) -> Result<Url, url::ParseError> {
+		authorize_url_with_base_url(base_url, client_id, &self.redirect_uri, state)
+	}
+
+	/// Waits for the browser to be redirected back, answers it, and returns the authorization
+	/// code.  Each connection is served in its own task, so one that never finishes its request
+	/// can't hold up the redirect.  Requests that carry neither a code nor an error (e.g.
+	/// `/favicon.ico`) get a 404 and ones with a missing or mismatched `state` get a 400; neither
+	/// ends the wait.
+	pub async fn accept_code(self, state: &str) -> Result<String, ListenerError> {
+		let state: Arc<str> = state.into();
+		let mut connections = JoinSet::new();
+		loop {
+			tokio::select! {
+				accepted = self.listener.accept() => {
+					let (stream, _) = accepted?;
+					connections.spawn(Self::serve(stream, state.clone()));
+				},
+				Some(served) = connections.join_next(), if (!connections.is_empty()) => {
+					// A connection that broke off says nothing about the authorization.
+					if let Ok(Ok(Some(result))) = served {
+						return result;
+					}
+				}
+			}
+		}
+	}
+
+	/// Answers a single connection, returning the outcome if it was the redirect.
+	async fn serve(mut stream: TcpStream, state: Arc<str>) -> io::Result<Option<Result<String, ListenerError>>> {
+		let mut buf = vec![0; 8192];
+		let mut len = 0;
+		while (!buf[..len].windows(4).any(|w| w == b"\r\n\r\n") && len < buf.len()) {
+			match stream.read(&mut buf[len..]).await? {
+				0 => break,
+				n => len += n
+			}
+		}
+		let request = String::from_utf8_lossy(&buf[..len]);
+		let target = request.lines().next().and_then(|line| line.split(' ').nth(1)).unwrap_or_default();
+		let query = Url::parse("http://localhost/").and_then(|base| base.join(target)).ok();
+		let param = |name: &str| query.as_ref().and_then(|url| url.query_pairs().find(|(k, _)| k == name).map(|(_, v)| v.into_owned()));
+
+		let (status, body, result) = match (param("code"), param("error")) {
+			(None, None) => ("404 Not Found", "", None),
+			_ if param("state").as_deref() != Some(&*state) => ("400 Bad Request", "Missing or mismatched state parameter.", None),
+			(_, Some(error)) => ("200 OK", "Authorization failed; check the application for details.", Some(Err(ListenerError::Denied(param("error_description").unwrap_or(error))))),
+			(Some(code), None) => ("200 OK", "Authorization complete; you may close this window.", Some(Ok(code)))
+		};
+		let response = format!("HTTP/1.1 {status}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len());
+		stream.write_all(response.as_bytes()).await?;
+		Ok(result)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_authorize_url() {
+		assert_eq!(
+			authorize_url("abc123", "http://localhost:8080/callback", "xyz").as_str(),
+			"https://api.enphaseenergy.com/oauth/authorize?response_type=code&client_id=abc123&redirect_uri=http%3A%2F%2Flocalhost%3A8080%2Fcallback&state=xyz"
+		);
+		assert_eq!(
+			authorize_url_with_base_url("http://127.0.0.1:8081/", "abc123", "http://localhost:8080/callback", "xyz")
+				.unwrap()
+				.as_str(),
+			"http://127.0.0.1:8081/oauth/authorize?response_type=code&client_id=abc123&redirect_uri=http%3A%2F%2Flocalhost%3A8080%2Fcallback&state=xyz"
+		);
+		assert!(authorize_url_with_base_url("not a url", "abc123", "http://localhost:8080/callback", "xyz").is_err());
+	}
+
+	#[cfg(feature = "oauth-listener")]
+	#[tokio::test]
+	async fn test_listener_accept_code() {
+		let listener = RedirectListener::bind("127.0.0.1:0").await.unwrap();
+		let uri = listener.redirect_uri().to_owned();
+		let browser = tokio::spawn(async move {
+			reqwest::get(format!("{uri}favicon.ico")).await.unwrap();
+			reqwest::get(format!("{uri}?code=the-code&state=xyz")).await.unwrap().text().await.unwrap()
+		});
+		assert_eq!(listener.accept_code("xyz").await.unwrap(), "the-code");
+		assert!(browser.await.unwrap().contains("complete"));
+	}
+
+	#[cfg(feature = "oauth-listener")]
+	#[tokio::test]
+	async fn test_listener_state_mismatch() {
+		let listener = RedirectListener::bind("127.0.0.1:0").await.unwrap();
+		let uri = listener.redirect_uri().to_owned();
+		let browser = tokio::spawn(async move {
+			let forged = reqwest::get(format!("{uri}?code=forged-code&state=forged")).await.unwrap().status();
+			let missing = reqwest::get(format!("{uri}?code=forged-code")).await.unwrap().status();
+			reqwest::get(format!("{uri}?code=the-code&state=xyz")).await.unwrap();
+			(forged, missing)
+		});
+		assert_eq!(listener.accept_code("xyz").await.unwrap(), "the-code");
+		assert_eq!(browser.await.unwrap(), (reqwest::StatusCode::BAD_REQUEST, reqwest::StatusCode::BAD_REQUEST));
+	}
+
+	#[cfg(feature = "oauth-listener")]
+	#[tokio::test]
+	async fn test_listener_stalled_connection() {
+		let listener = RedirectListener::bind("127.0.0.1:0").await.unwrap();
+		let uri = listener.redirect_uri().to_owned();
+		let addr = uri.trim_start_matches("http://").trim_end_matches('/').to_owned();
+		let browser = tokio::spawn(async move {
+			let mut stalled = TcpStream::connect(addr).await.unwrap();
+			stalled.write_all(b"GET /?code=stalled").await.unwrap();
+			reqwest::get(format!("{uri}?code=the-code&state=xyz")).await.unwrap();
+			stalled
+		});
+		assert_eq!(listener.accept_code("xyz").await.unwrap(), "the-code");
+		browser.await.unwrap();
+	}
+}
@@ -0,0 +1,30 @@
+use serde::Deserialize;
+
+/// One 1-based page of a paged listing, e.g. [`super::System::get_events_page`].
+#[derive(Clone, Debug, Deserialize)]
+pub struct Page<T> {
+	/// Items on all pages.
+	pub total: u32,
+	/// 1-based.
+	pub current_page: u32,
+	pub size: u32,
+	/// Items on this page.
+	pub count: u32,
+	/// The API names the array after what it lists, and names that key in a separate `items`
+	/// string.
+	#[serde(rename = "events", alias = "alarms", alias = "sessions", alias = "systems")]
+	pub items: Vec<T>
+}
+
+impl<T> Page<T> {
+	#[inline]
+	pub(crate) fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+		Page {
+			total: self.total,
+			current_page: self.current_page,
+			size: self.size,
+			count: self.count,
+			items: self.items.into_iter().map(f).collect()
+		}
+	}
+}
@@ -0,0 +1,230 @@
+use std::fmt;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use arcstr::ArcStr;
+use base64::engine::Engine;
+use chrono::Duration;
+use chrono::Utc;
+use reqwest::Method;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::credentials::CredentialProvider;
+use crate::credentials::Credentials;
+use super::AuthResponse;
+use super::Error;
+use super::Governor;
+use super::Overflow;
+use super::TokenStore;
+use super::Tokens;
+
+/// State shared between a [`super::Client`] and every [`super::System`] it hands out, so that
+/// a token refresh through any of them is visible to all.
+pub(crate) struct Session {
+	pub(crate) client: reqwest::Client,
+	base_url: RwLock<ArcStr>,
+	pub(crate) api_key_qstr: ArcStr,
+	page_size: AtomicU32,
+	credential_provider: Arc<dyn CredentialProvider>,
+	/// Held for the duration of every token request; also guards the cached client credentials.
+	credentials: Mutex<Credentials>,
+	tokens: RwLock<Tokens>,
+	token_store: RwLock<Option<Arc<dyn TokenStore>>>,
+	governor: RwLock<Option<Arc<Governor>>>
+}
+
+impl Session {
+	#[inline]
+	pub(crate) fn new(client: reqwest::Client, base_url: &str, api_key: &str, credential_provider: Arc<dyn CredentialProvider>, credentials: Credentials, tokens: Tokens) -> Self {
+		Self {
+			client,
+			base_url: RwLock::new(base_url.trim_end_matches('/').into()),
+			api_key_qstr: format!("key={api_key}").into(),
+			page_size: AtomicU32::new(super::DEFAULT_PAGE_SIZE),
+			credential_provider,
+			credentials: Mutex::new(credentials),
+			tokens: RwLock::new(tokens),
+			token_store: RwLock::new(None),
+			governor: RwLock::new(None)
+		}
+	}
+
+	/// The API base URL, without a trailing slash.
+	#[inline]
+	pub(crate) fn base_url(&self) -> ArcStr {
+		self.base_url.read().unwrap().clone()
+	}
+
+	#[inline]
+	pub(crate) fn set_base_url(&self, base_url: &str) {
+		*self.base_url.write().unwrap() = base_url.trim_end_matches('/').into();
+	}
+
+	#[inline]
+	pub(crate) fn page_size(&self) -> u32 {
+		self.page_size.load(Ordering::Relaxed)
+	}
+
+	#[inline]
+	pub(crate) fn set_page_size(&self, page_size: u32) {
+		self.page_size.store(page_size, Ordering::Relaxed);
+	}
+
+	#[inline]
+	pub(crate) fn tokens(&self) -> Tokens {
+		self.tokens.read().unwrap().clone()
+	}
+
+	pub(crate) fn set_token_store(&self, store: Arc<dyn TokenStore>) -> Result<(), Error> {
+		store.save(&self.tokens()).map_err(Error::TokenStore)?;
+		*self.token_store.write().unwrap() = Some(store);
+		Ok(())
+	}
+
+	#[inline]
+	pub(crate) fn set_governor(&self, governor: Arc<Governor>) {
+		*self.governor.write().unwrap() = Some(governor);
+	}
+
+	/// Refreshes the tokens unless they've already been refreshed since `stale_access` was
+	/// handed out, so that concurrent callers who all saw the same expired token share a single
+	/// refresh.
+	pub(crate) async fn refresh(&self, stale_access: &str) -> Result<Tokens, Error> {
+		let mut credentials = self.credentials.lock().await;
+		let current = self.tokens();
+		if (current.access != stale_access) {
+			return Ok(current);
+		}
+		let url = format!("{}/oauth/token?grant_type=refresh_token&refresh_token={}", self.base_url(), current.refresh);
+		let response = Self::request_tokens(&self.client, &url, &self.credential_provider, &mut credentials).await?;
+		let tokens = Tokens::from(response);
+		*self.tokens.write().unwrap() = tokens.clone();
+		let store = self.token_store.read().unwrap().clone();
+		if let Some(store) = store {
+			// Saved off the runtime, but still under the lock so that saves land in refresh order.
+			let saved = tokens.clone();
+			crate::unblock(move || store.save(&saved)).await.map_err(Error::TokenStore)?;
+		}
+		Ok(tokens)
+	}
+
+	/// Returns the current tokens, refreshing first if the access token is about to expire.
+	async fn fresh_tokens(&self) -> Result<Tokens, Error> {
+		let tokens = self.tokens();
+		if (tokens.expires_at.is_some_and(|expires_at| expires_at - Duration::minutes(5) <= Utc::now())) {
+			return self.refresh(&tokens.access).await;
+		}
+		Ok(tokens)
+	}
+
+	/// GETs `url` with the bearer token; if the API rejects the token, refreshes and retries once.
+	#[inline]
+	pub(crate) async fn get<T: DeserializeOwned>(&self, url: &str) -> Result<T, Error> {
+		self.request::<(), T>(Method::GET, url, None).await
+	}
+
+	/// PUTs `body` as JSON to `url`, the same way as [`Session::get`].
+	#[cfg(feature = "control")]
+	#[inline]
+	pub(crate) async fn put<B: Serialize, T: DeserializeOwned>(&self, url: &str, body: &B) -> Result<T, Error> {
+		self.request(Method::PUT, url, Some(body)).await
+	}
+
+	/// POSTs `body`, if any, as JSON to `url`, the same way as [`Session::get`].
+	#[cfg(feature = "control")]
+	#[inline]
+	pub(crate) async fn post<B: Serialize, T: DeserializeOwned>(&self, url: &str, body: Option<&B>) -> Result<T, Error> {
+		self.request(Method::POST, url, body).await
+	}
+
+	async fn request<B: Serialize, T: DeserializeOwned>(&self, method: Method, url: &str, body: Option<&B>) -> Result<T, Error> {
+		let tokens = self.fresh_tokens().await?;
+		let response = self.send(&method, url, body, &tokens.access).await?;
+		let response = match response.status() {
+			StatusCode::UNAUTHORIZED => {
+				let tokens = self.refresh(&tokens.access).await?;
+				self.send(&method, url, body, &tokens.access).await?
+			},
+			_ => response
+		};
+		Ok(response.error_for_status()?.json().await?)
+	}
+
+	/// Sends a single request, subject to the governor if there is one.  A 429 becomes
+	/// [`Error::RateLimited`], unless the governor queues requests, in which case it waits out
+	/// `Retry-After` and tries once more.
+	async fn send<B: Serialize>(&self, method: &Method, url: &str, body: Option<&B>, access: &str) -> Result<reqwest::Response, Error> {
+		let governor = self.governor.read().unwrap().clone();
+		let attempts = match governor.as_ref().map(|g| g.overflow()) {
+			Some(Overflow::Queue) => 2,
+			_ => 1
+		};
+		let mut retry_after = None;
+		for _ in 0..attempts {
+			if let Some(governor) = &governor {
+				governor.acquire().await?;
+			}
+			let mut request = self.client.request(method.clone(), url).bearer_auth(access);
+			if let Some(body) = body {
+				request = request.json(body);
+			}
+			let response = request.send().await?;
+			if (response.status() != StatusCode::TOO_MANY_REQUESTS) {
+				return Ok(response);
+			}
+			retry_after = Self::retry_after(&response);
+			if let Some(governor) = &governor {
+				governor.throttled(retry_after.unwrap_or(std::time::Duration::from_secs(60)));
+			}
+		}
+		Err(Error::RateLimited(retry_after))
+	}
+
+	/// Parses `Retry-After`, which may be either a number of seconds or an HTTP date.
+	fn retry_after(response: &reqwest::Response) -> Option<std::time::Duration> {
+		let value = response.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+		match value.parse() {
+			Ok(seconds) => Some(std::time::Duration::from_secs(seconds)),
+			Err(_) => (chrono::DateTime::parse_from_rfc2822(value).ok()? - Utc::now().fixed_offset()).to_std().ok()
+		}
+	}
+
+	/// POSTs to the token endpoint, re-querying the credential provider and retrying once if
+	/// the cached client credentials are rejected.
+	pub(crate) async fn request_tokens(client: &reqwest::Client, url: &str, provider: &Arc<dyn CredentialProvider>, credentials: &mut Credentials) -> Result<AuthResponse, Error> {
+		let response = client.post(url).header("Authorization", Self::token_auth_header(credentials)).send().await?;
+		let response = match response.status() {
+			StatusCode::UNAUTHORIZED => {
+				let fresh = crate::credentials::query(provider).await?;
+				if (fresh == *credentials) {
+					response
+				} else {
+					*credentials = fresh;
+					client.post(url).header("Authorization", Self::token_auth_header(credentials)).send().await?
+				}
+			},
+			_ => response
+		};
+		Ok(response.error_for_status()?.json().await?)
+	}
+
+	#[inline]
+	fn token_auth_header(credentials: &Credentials) -> String {
+		format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", credentials.username, credentials.password)))
+	}
+}
+
+impl fmt::Debug for Session {
+	#[inline]
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Session")
+			.field("base_url", &self.base_url())
+			.field("api_key_qstr", &"<redacted>")
+			.finish_non_exhaustive()
+	}
+}
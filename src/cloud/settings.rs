@@ -0,0 +1,191 @@
+
+use chrono::DateTime;
+use chrono::Utc;
+use compact_str::CompactString;
+use serde::de;
+use serde::Deserialize;
+use serde::Deserializer;
+#[cfg(feature = "control")] use serde::Serialize;
+use serde::Serializer;
+use serde_with::serde_as;
+use serde_with::DeserializeAs;
+use serde_with::DeserializeFromStr;
+use serde_with::DisplayFromStr;
+use serde_with::SerializeAs;
+use serde_with::SerializeDisplay;
+use serde_with::TimestampSeconds;
+use strum::Display;
+use strum::EnumString;
+
+/// `"enabled"` or `"disabled"`, as a `bool`.
+struct Enabled;
+
+impl SerializeAs<bool> for Enabled {
+	#[inline]
+	fn serialize_as<S: Serializer>(source: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(match source {
+			true => "enabled",
+			false => "disabled"
+		})
+	}
+}
+
+impl<'de> DeserializeAs<'de, bool> for Enabled {
+	fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+		match CompactString::deserialize(deserializer)?.as_str() {
+			"enabled" => Ok(true),
+			"disabled" => Ok(false),
+			s => Err(de::Error::invalid_value(de::Unexpected::Str(s), &"\"enabled\" or \"disabled\""))
+		}
+	}
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Display, EnumString, DeserializeFromStr, SerializeDisplay)]
+pub enum BatteryProfile {
+	/// Store excess solar and use it to power the home.
+	#[strum(serialize = "Self - Consumption")]
+	SelfConsumption,
+	/// Use stored energy when grid prices are highest.
+	#[strum(serialize = "Savings Mode")]
+	Savings,
+	/// Keep the batteries full for outages.
+	#[strum(serialize = "Full Backup")]
+	FullBackup,
+	#[strum(default)]
+	Other(String)
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct BatterySettings {
+	#[serde(rename = "battery_mode")]
+	pub profile: BatteryProfile,
+	/// State of charge kept back for outages.
+	#[serde(rename = "reserve_soc")]
+	pub reserve_percent: u8,
+	#[serde_as(as = "Enabled")]
+	pub energy_independence: bool,
+	#[serde_as(as = "Enabled")]
+	pub charge_from_grid: bool,
+	/// State of charge at which the batteries shut down during an outage.
+	#[serde(rename = "battery_shutdown_level")]
+	pub shutdown_percent: Option<u8>
+}
+
+/// Changes for [`super::System::set_battery_settings`].  Settings left as `None` are not
+/// changed.
+#[cfg(feature = "control")]
+#[serde_as]
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BatterySettingsUpdate {
+	#[serde(rename = "battery_mode", skip_serializing_if = "Option::is_none")]
+	pub profile: Option<BatteryProfile>,
+	#[serde(rename = "reserve_soc", skip_serializing_if = "Option::is_none")]
+	pub reserve_percent: Option<u8>,
+	#[serde_as(as = "Option<Enabled>")]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub energy_independence: Option<bool>,
+	#[serde_as(as = "Option<Enabled>")]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub charge_from_grid: Option<bool>
+}
+
+/// Storm Guard charges the batteries to full when a severe weather alert is issued.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct StormGuard {
+	#[serde_as(as = "Enabled")]
+	#[serde(rename = "storm_guard_status")]
+	pub enabled: bool,
+	/// Whether there's an active alert for the site.
+	#[serde_as(as = "DisplayFromStr")]
+	#[serde(rename = "storm_alert")]
+	pub alert_active: bool
+}
+
+#[cfg(feature = "control")]
+#[serde_as]
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct StormGuardUpdate {
+	#[serde_as(as = "Enabled")]
+	pub storm_guard_status: bool
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct GridStatus {
+	/// E.g. `On Grid` or `Off Grid`.
+	#[serde(rename = "grid_state")]
+	pub state: CompactString,
+	#[serde_as(as = "TimestampSeconds<i64>")]
+	#[serde(rename = "last_report_date")]
+	pub last_report_at: DateTime<Utc>
+}
+
+impl GridStatus {
+	#[inline]
+	pub fn is_on_grid(&self) -> bool {
+		self.state == "On Grid"
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct LoadControlResponse {
+	//system_id: u32,
+	pub load_control_data: Vec<LoadControlCircuit>
+}
+
+/// A load shed by the system controller when the batteries run low.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LoadControlCircuit {
+	/// E.g. `NC1`.
+	pub name: CompactString,
+	pub load_name: CompactString,
+	/// E.g. `Basic`, `Advanced` or `Scheduled`.
+	pub mode: CompactString,
+	/// State of charge below which the load is shed, in `Advanced` mode.
+	pub soc_low: Option<u8>,
+	/// State of charge above which the load is restored, in `Advanced` mode.
+	pub soc_high: Option<u8>
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_deserialize_battery_settings() {
+		let settings: BatterySettings = serde_json::from_str(include_str!("mock/testdata/battery_settings.json")).unwrap();
+		assert_eq!(settings.profile, BatteryProfile::SelfConsumption);
+		assert_eq!(settings.reserve_percent, 30);
+		assert!(settings.energy_independence);
+		assert!(!settings.charge_from_grid);
+	}
+
+	#[test]
+	fn test_battery_profile_round_trip() {
+		for profile in [BatteryProfile::SelfConsumption, BatteryProfile::Savings, BatteryProfile::FullBackup, BatteryProfile::Other("Sell Everything".into())] {
+			assert_eq!(profile.to_string().parse::<BatteryProfile>().unwrap(), profile);
+		}
+		assert_eq!(BatteryProfile::Savings.to_string(), "Savings Mode");
+		assert_eq!(BatteryProfile::Other("Sell Everything".into()).to_string(), "Sell Everything");
+	}
+
+	#[test]
+	fn test_deserialize_storm_guard() {
+		let storm_guard: StormGuard = serde_json::from_str(include_str!("mock/testdata/storm_guard.json")).unwrap();
+		assert!(storm_guard.enabled);
+		assert!(!storm_guard.alert_active);
+	}
+
+	#[cfg(feature = "control")]
+	#[test]
+	fn test_serialize_battery_settings_update() {
+		let update = BatterySettingsUpdate {
+			profile: Some(BatteryProfile::FullBackup),
+			charge_from_grid: Some(true),
+			..Default::default()
+		};
+		assert_eq!(serde_json::to_string(&update).unwrap(), r#"{"battery_mode":"Full Backup","charge_from_grid":"enabled"}"#);
+	}
+}
@@ -1,29 +1,59 @@
 use std::borrow::Cow;
 use std::sync::Arc;
 
-use arcstr::ArcStr;
 use chrono::serde::ts_seconds;
 use chrono::serde::ts_seconds_option;
 use chrono::DateTime;
 use chrono::Days;
+use chrono::Duration;
 use chrono::NaiveDate;
 use chrono::NaiveTime;
+use chrono::Offset;
+use chrono::TimeZone;
 use chrono::Utc;
+use chrono_tz::Tz;
 use compact_str::CompactString;
 use serde::Deserialize;
 use serde_with::serde_as;
 use serde_with::TimestampSeconds;
-use tokio::sync::Mutex;
 
 use super::ConnectionType;
+use super::Error;
 use super::Granularity;
+use super::Session;
+use super::all_pages;
+use super::devices::Devices;
+use super::devices::DevicesResponse;
+use super::ev_charger::ChargingSession;
+use super::ev_charger::EvCharger;
+use super::ev_charger::EvChargerLifetimeResponse;
+use super::ev_charger::EvChargerStatus;
+use super::ev_charger::EvChargersResponse;
+#[cfg(feature = "control")] use super::ev_charger::StartChargingRequest;
+use super::events::Alarm;
+use super::events::Event;
+use super::page::Page;
+#[cfg(feature = "control")] use super::settings::BatterySettingsUpdate;
+use super::settings::BatterySettings;
+use super::settings::GridStatus;
+use super::settings::LoadControlCircuit;
+use super::settings::LoadControlResponse;
+use super::settings::StormGuard;
+#[cfg(feature = "control")] use super::settings::StormGuardUpdate;
+use super::telemetry::BatteryInterval;
+use super::telemetry::ConsumptionMeterInterval;
+use super::telemetry::GridInterval;
+use super::telemetry::GridTelemetryResponse;
+use super::telemetry::LatestTelemetry;
+use super::telemetry::LatestTelemetryResponse;
+use super::telemetry::MicroinverterProduction;
+use super::telemetry::ProductionMeterInterval;
+use super::telemetry::TelemetryResponse;
 use crate::DATE_FORMAT;
 
 #[derive(Clone, Debug)]
 pub struct System {
-	client: reqwest::Client,
-	api_key_qstr: ArcStr,
-	auth_header: Arc<Mutex<String>>,
+	session: Arc<Session>,
 	pub system_id: u32,
 	pub name: CompactString,
 	pub public_name: CompactString,
@@ -43,95 +73,343 @@ pub struct System {
 }
 
 impl System {
-	pub async fn get_summary(&self) -> Result<SystemSummary, reqwest::Error> {
-		// TODO:  Handle pagination
-		self.client
-			.get(format!("https://api.enphaseenergy.com/api/v4/systems/{}/summary?{}&size=100", self.system_id, self.api_key_qstr))
-			.header("Authorization", &*self.auth_header.lock().await)
-			.send()
-			.await?
-			.error_for_status()?
-			.json()
+	/// The system's IANA timezone, in which the API's dates are expressed.
+	#[inline]
+	pub fn tz(&self) -> Result<Tz, Error> {
+		self.timezone.parse().map_err(|_| Error::InvalidTimezone(self.timezone.to_string()))
+	}
+
+	/// The date at `at` in the system's timezone, e.g. for the `start_date` of a request.
+	#[inline]
+	pub fn local_date(&self, at: DateTime<Utc>) -> Result<NaiveDate, Error> {
+		Ok(at.with_timezone(&self.tz()?).date_naive())
+	}
+
+	pub async fn get_summary(&self) -> Result<SystemSummary, Error> {
+		self.session
+			.get(&format!("{}/api/v4/systems/{}/summary?{}", self.session.base_url(), self.system_id, self.session.api_key_qstr))
 			.await
 	}
 
-	pub async fn get_lifetime_production(&self, start_date: Option<&NaiveDate>, end_date: Option<&NaiveDate>, include_split_meter_and_microinverters: bool) -> Result<Vec<(DateTime<Utc>, u32)>, reqwest::Error> {
+	/// The latest power reading from each device, updated more often than
+	/// [`SystemSummary::current_power`].
+	pub async fn get_latest_telemetry(&self) -> Result<LatestTelemetry, Error> {
+		let response: LatestTelemetryResponse = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/latest_telemetry?{}", self.session.base_url(), self.system_id, self.session.api_key_qstr))
+			.await?;
+		Ok(response.devices)
+	}
+
+	/// Daily production.  Dates are in the system's timezone, both here and in the results.
+	pub async fn get_lifetime_production(&self, start_date: Option<&NaiveDate>, end_date: Option<&NaiveDate>, include_split_meter_and_microinverters: bool) -> Result<LifetimeEnergy, Error> {
+		let tz = self.tz()?;
+		let mut args = self.date_range_args(start_date, end_date);
+		if (include_split_meter_and_microinverters) {
+			args.push(Cow::Borrowed("production=all"));
+		}
+		let response: LifetimeProductionResponse = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/energy_lifetime?{}", self.session.base_url(), self.system_id, args.join("&")))
+			.await?;
+		Ok(LifetimeEnergy {
+			days: daily(tz, response.start_date, response.production),
+			meta: response.meta
+		})
+	}
+
+	/// Daily consumption.  Dates are in the system's timezone, both here and in the results.
+	pub async fn get_lifetime_consumption(&self, start_date: Option<&NaiveDate>, end_date: Option<&NaiveDate>) -> Result<LifetimeEnergy, Error> {
+		let tz = self.tz()?;
+		let args = self.date_range_args(start_date, end_date);
+		let response: LifetimeConsumptionResponse = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/consumption_lifetime?{}", self.session.base_url(), self.system_id, args.join("&")))
+			.await?;
+		Ok(LifetimeEnergy {
+			days: daily(tz, response.start_date, response.consumption),
+			meta: response.meta
+		})
+	}
+
+	pub async fn get_microinverter_production(&self, start_date: &NaiveDate, granularity: Option<Granularity>) -> Result<Vec<MicroinverterProduction>, Error> {
+		let args = self.telemetry_args(start_date, granularity);
+		let response: TelemetryResponse<MicroinverterProduction> = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/telemetry/production_micro?{}", self.session.base_url(), self.system_id, args.join("&")))
+			.await?;
+		Ok(response.intervals)
+	}
+
+	pub async fn get_consumption_meter_telemetry(&self, start_date: &NaiveDate, granularity: Option<Granularity>) -> Result<Vec<ConsumptionMeterInterval>, Error> {
+		let args = self.telemetry_args(start_date, granularity);
+		let response: TelemetryResponse<ConsumptionMeterInterval> = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/telemetry/consumption_meter?{}", self.session.base_url(), self.system_id, args.join("&")))
+			.await?;
+		Ok(response.intervals)
+	}
+
+	pub async fn get_production_meter_telemetry(&self, start_date: &NaiveDate, granularity: Option<Granularity>) -> Result<Vec<ProductionMeterInterval>, Error> {
+		let args = self.telemetry_args(start_date, granularity);
+		let response: TelemetryResponse<ProductionMeterInterval> = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/telemetry/production_meter?{}", self.session.base_url(), self.system_id, args.join("&")))
+			.await?;
+		Ok(response.intervals)
+	}
+
+	pub async fn get_battery_telemetry(&self, start_date: &NaiveDate, granularity: Option<Granularity>) -> Result<Vec<BatteryInterval>, Error> {
+		let args = self.telemetry_args(start_date, granularity);
+		let response: TelemetryResponse<BatteryInterval> = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/telemetry/battery?{}", self.session.base_url(), self.system_id, args.join("&")))
+			.await?;
+		Ok(response.intervals)
+	}
+
+	/// Energy imported from the grid.
+	pub async fn get_energy_import_telemetry(&self, start_date: &NaiveDate, granularity: Option<Granularity>) -> Result<Vec<GridInterval>, Error> {
+		let args = self.telemetry_args(start_date, granularity);
+		let response: GridTelemetryResponse = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/energy_import_telemetry?{}", self.session.base_url(), self.system_id, args.join("&")))
+			.await?;
+		Ok(response.intervals.into_iter().flatten().collect())
+	}
+
+	/// Energy exported to the grid.
+	pub async fn get_energy_export_telemetry(&self, start_date: &NaiveDate, granularity: Option<Granularity>) -> Result<Vec<GridInterval>, Error> {
+		let args = self.telemetry_args(start_date, granularity);
+		let response: GridTelemetryResponse = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/energy_export_telemetry?{}", self.session.base_url(), self.system_id, args.join("&")))
+			.await?;
+		Ok(response.intervals.into_iter().flatten().collect())
+	}
+
+	pub async fn get_devices(&self) -> Result<Devices, Error> {
+		let response: DevicesResponse = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/devices?{}", self.session.base_url(), self.system_id, self.session.api_key_qstr))
+			.await?;
+		Ok(response.devices)
+	}
+
+	/// Telemetry for a single microinverter, by serial number.
+	pub async fn get_microinverter_telemetry(&self, serial_number: &str, start_date: &NaiveDate, granularity: Option<Granularity>) -> Result<Vec<MicroinverterProduction>, Error> {
+		let args = self.telemetry_args(start_date, granularity);
+		let response: TelemetryResponse<MicroinverterProduction> = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/devices/micros/{serial_number}/telemetry?{}", self.session.base_url(), self.system_id, args.join("&")))
+			.await?;
+		Ok(response.intervals)
+	}
+
+	/// Events that started between `start_time` and `end_time` (or now), from every page.
+	pub async fn get_events(&self, start_time: DateTime<Utc>, end_time: Option<DateTime<Utc>>) -> Result<Vec<Event>, Error> {
+		all_pages(|page| self.get_events_page(start_time, end_time, page)).await
+	}
+
+	/// Fetches a single 1-based page of events.
+	pub async fn get_events_page(&self, start_time: DateTime<Utc>, end_time: Option<DateTime<Utc>>, page: u32) -> Result<Page<Event>, Error> {
+		let mut url = format!(
+			"{}/api/v4/systems/{}/events?{}&start_time={}&page={page}&size={}",
+			self.session.base_url(),
+			self.system_id,
+			self.session.api_key_qstr,
+			start_time.timestamp(),
+			self.session.page_size()
+		);
+		if let Some(end_time) = end_time {
+			url.push_str(&format!("&end_time={}", end_time.timestamp()));
+		}
+		self.session.get(&url).await
+	}
+
+	/// Open and closed alarms, from every page.  See [`Alarm::is_open`].
+	pub async fn get_alarms(&self) -> Result<Vec<Alarm>, Error> {
+		all_pages(|page| self.get_alarms_page(page)).await
+	}
+
+	/// Fetches a single 1-based page of alarms.
+	pub async fn get_alarms_page(&self, page: u32) -> Result<Page<Alarm>, Error> {
+		let url = format!("{}/api/v4/systems/{}/alarms?{}&page={page}&size={}", self.session.base_url(), self.system_id, self.session.api_key_qstr, self.session.page_size());
+		self.session.get(&url).await
+	}
+
+	pub async fn get_battery_settings(&self) -> Result<BatterySettings, Error> {
+		self.session.get(&self.config_url("battery_settings")).await
+	}
+
+	/// Changes the battery profile, reserve or grid charging, returning the new settings.
+	#[cfg(feature = "control")]
+	pub async fn set_battery_settings(&self, update: &BatterySettingsUpdate) -> Result<BatterySettings, Error> {
+		self.session.put(&self.config_url("battery_settings"), update).await
+	}
+
+	pub async fn get_storm_guard(&self) -> Result<StormGuard, Error> {
+		self.session.get(&self.config_url("storm_guard")).await
+	}
+
+	#[cfg(feature = "control")]
+	pub async fn set_storm_guard(&self, enabled: bool) -> Result<StormGuard, Error> {
+		self.session.put(&self.config_url("storm_guard"), &StormGuardUpdate { storm_guard_status: enabled }).await
+	}
+
+	pub async fn get_grid_status(&self) -> Result<GridStatus, Error> {
+		self.session.get(&self.config_url("grid_status")).await
+	}
+
+	pub async fn get_load_control(&self) -> Result<Vec<LoadControlCircuit>, Error> {
+		let response: LoadControlResponse = self.session.get(&self.config_url("load_control")).await?;
+		Ok(response.load_control_data)
+	}
+
+	pub async fn get_ev_chargers(&self) -> Result<Vec<EvCharger>, Error> {
+		let response: EvChargersResponse = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/ev_charger/devices?{}", self.session.base_url(), self.system_id, self.session.api_key_qstr))
+			.await?;
+		Ok(response.devices)
+	}
+
+	pub async fn get_ev_charger_status(&self, serial_number: &str) -> Result<EvChargerStatus, Error> {
+		self.session.get(&self.ev_charger_url(serial_number, "status")).await
+	}
+
+	/// Charging sessions, newest last, from every page.
+	pub async fn get_charging_sessions(&self, serial_number: &str) -> Result<Vec<ChargingSession>, Error> {
+		all_pages(|page| self.get_charging_sessions_page(serial_number, page)).await
+	}
+
+	/// Fetches a single 1-based page of charging sessions.
+	pub async fn get_charging_sessions_page(&self, serial_number: &str, page: u32) -> Result<Page<ChargingSession>, Error> {
+		let url = format!("{}&page={page}&size={}", self.ev_charger_url(serial_number, "sessions"), self.session.page_size());
+		self.session.get(&url).await
+	}
+
+	/// Energy delivered by a charger each day.  Dates are in the system's timezone.
+	pub async fn get_ev_charger_lifetime(&self, serial_number: &str, start_date: Option<&NaiveDate>, end_date: Option<&NaiveDate>) -> Result<Vec<DailyEnergy>, Error> {
+		let tz = self.tz()?;
+		let args = self.date_range_args(start_date, end_date);
+		let response: EvChargerLifetimeResponse = self
+			.session
+			.get(&format!("{}/api/v4/systems/{}/ev_charger/{serial_number}/lifetime?{}", self.session.base_url(), self.system_id, args.join("&")))
+			.await?;
+		Ok(daily(tz, response.start_date, response.consumption))
+	}
+
+	/// Starts charging, optionally limiting the current to `charging_level` amps.
+	#[cfg(feature = "control")]
+	pub async fn start_charging(&self, serial_number: &str, charging_level: Option<u8>) -> Result<(), Error> {
+		let request = StartChargingRequest { charging_level, connector_id: 1 };
+		let _: serde::de::IgnoredAny = self.session.post(&self.ev_charger_url(serial_number, "start_charging"), Some(&request)).await?;
+		Ok(())
+	}
+
+	#[cfg(feature = "control")]
+	pub async fn stop_charging(&self, serial_number: &str) -> Result<(), Error> {
+		let _: serde::de::IgnoredAny = self.session.post(&self.ev_charger_url(serial_number, "stop_charging"), None::<&()>).await?;
+		Ok(())
+	}
+
+	#[inline]
+	fn ev_charger_url(&self, serial_number: &str, endpoint: &str) -> String {
+		format!("{}/api/v4/systems/{}/ev_charger/{serial_number}/{endpoint}?{}", self.session.base_url(), self.system_id, self.session.api_key_qstr)
+	}
+
+	#[inline]
+	fn config_url(&self, setting: &str) -> String {
+		format!("{}/api/v4/systems/config/{}/{setting}?{}", self.session.base_url(), self.system_id, self.session.api_key_qstr)
+	}
+
+	fn date_range_args(&self, start_date: Option<&NaiveDate>, end_date: Option<&NaiveDate>) -> Vec<Cow<'_, str>> {
 		let mut args = Vec::with_capacity(4);
-		args.push(Cow::Borrowed(self.api_key_qstr.as_ref()));
+		args.push(Cow::Borrowed(self.session.api_key_qstr.as_ref()));
 		if let Some(date) = start_date {
 			args.push(Cow::Owned(format!("start_date={}", date.format(DATE_FORMAT))));
 		}
 		if let Some(date) = end_date {
 			args.push(Cow::Owned(format!("end_date={}", date.format(DATE_FORMAT))));
 		}
-		if (include_split_meter_and_microinverters) {
-			args.push(Cow::Borrowed("production=all"));
-		}
-		let response: LifetimeProductionResponse = self
-			.client
-			.get(format!("https://api.enphaseenergy.com/api/v4/systems/{}/energy_lifetime?{}", self.system_id, args.join("&")))
-			.header("Authorization", &*self.auth_header.lock().await)
-			.send()
-			.await?
-			.error_for_status()?
-			.json()
-			.await?;
-		let start_date = response.start_date;
-		let result = response
-			.production
-			.into_iter()
-			.enumerate()
-			.map(|(i, prod)| {
-				let date = start_date + Days::new(i as u64);
-				(date.and_time(NaiveTime::default()).and_local_timezone(Utc).unwrap(), prod)
-			})
-			.collect();
-		Ok(result)
-	}
-
-	pub async fn get_microinverter_production(&self, start_date: &NaiveDate, granularity: Option<Granularity>) -> Result<Vec<MicroinverterProduction>, reqwest::Error> {
+		args
+	}
+
+	fn telemetry_args(&self, start_date: &NaiveDate, granularity: Option<Granularity>) -> Vec<Cow<'_, str>> {
 		let mut args = Vec::with_capacity(3);
-		args.push(Cow::Borrowed(self.api_key_qstr.as_ref()));
+		args.push(Cow::Borrowed(self.session.api_key_qstr.as_ref()));
 		args.push(Cow::Owned(format!("start_date={}", start_date.format(DATE_FORMAT))));
 		if let Some(granularity) = granularity {
 			args.push(Cow::Owned(format!("granularity={granularity}")));
 		}
-		let response: MicroinverterProductionResponse = self
-			.client
-			.get(format!("https://api.enphaseenergy.com/api/v4/systems/{}/telemetry/production_micro?{}", self.system_id, args.join("&")))
-			.header("Authorization", &*self.auth_header.lock().await)
-			.send()
-			.await?
-			.error_for_status()?
-			.json()
-			.await?;
-		Ok(response.intervals)
+		args
 	}
 }
 
-impl From<(reqwest::Client, ArcStr, Arc<Mutex<String>>, SystemResponse)> for System {
+/// Pairs each of a series of daily values with its date and the start of that day in `tz`.
+/// Every value is kept, so a date the timezone skipped entirely shares its start with the next
+/// date; see [`DailyEnergy::start`].
+fn daily(tz: Tz, start_date: NaiveDate, values: Vec<u32>) -> Vec<DailyEnergy> {
+	values
+		.into_iter()
+		.enumerate()
+		.map(|(i, watt_hours)| {
+			let date = start_date + Days::new(i as u64);
+			DailyEnergy { date, start: start_of_day(tz, date), watt_hours }
+		})
+		.collect()
+}
+
+/// Local midnight, or the end of the gap if the clocks skip midnight (for DST, or like Samoa,
+/// skipping the whole day).
+fn start_of_day(tz: Tz, date: NaiveDate) -> DateTime<Tz> {
+	let midnight = date.and_time(NaiveTime::MIN);
+	tz.from_local_datetime(&midnight).earliest().unwrap_or_else(|| {
+		// Reading midnight with the offset from before the gap lands on the first instant after it.
+		let before = tz.offset_from_utc_datetime(&(midnight - Duration::days(1))).fix();
+		tz.from_utc_datetime(&(midnight - Duration::seconds(before.local_minus_utc().into())))
+	})
+}
+
+/// Energy over one day in the system's timezone.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DailyEnergy {
+	pub date: NaiveDate,
+	/// The start of `date` in the system's timezone.  A date the timezone skipped entirely (e.g.
+	/// 2011-12-30 in Samoa) has no instants of its own, so it gets the same start as the date
+	/// after it; use `date`, not `start`, to tell such entries apart.
+	pub start: DateTime<Tz>,
+	pub watt_hours: u32
+}
+
+#[derive(Clone, Debug)]
+pub struct LifetimeEnergy {
+	pub days: Vec<DailyEnergy>,
+	pub meta: Metadata
+}
+
+impl From<(Arc<Session>, SystemResponse)> for System {
 	#[inline]
-	fn from(input: (reqwest::Client, ArcStr, Arc<Mutex<String>>, SystemResponse)) -> Self {
+	fn from(input: (Arc<Session>, SystemResponse)) -> Self {
 		Self {
-			client: input.0,
-			api_key_qstr: input.1,
-			auth_header: input.2,
-			system_id: input.3.system_id,
-			name: input.3.name,
-			public_name: input.3.public_name,
-			timezone: input.3.timezone,
-			address: input.3.address,
-			connection_type: input.3.connection_type,
-			status: input.3.status,
-			last_report_at: input.3.last_report_at,
-			last_energy_at: input.3.last_energy_at,
-			operational_at: input.3.operational_at,
-			attachment_type: input.3.attachment_type,
-			interconnect_date: input.3.interconnect_date,
-			other_references: input.3.other_references,
-			energy_lifetime: input.3.energy_lifetime,
-			energy_today: input.3.energy_today,
-			system_size: input.3.system_size
+			session: input.0,
+			system_id: input.1.system_id,
+			name: input.1.name,
+			public_name: input.1.public_name,
+			timezone: input.1.timezone,
+			address: input.1.address,
+			connection_type: input.1.connection_type,
+			status: input.1.status,
+			last_report_at: input.1.last_report_at,
+			last_energy_at: input.1.last_energy_at,
+			operational_at: input.1.operational_at,
+			attachment_type: input.1.attachment_type,
+			interconnect_date: input.1.interconnect_date,
+			other_references: input.1.other_references,
+			energy_lifetime: input.1.energy_lifetime,
+			energy_today: input.1.energy_today,
+			system_size: input.1.system_size
 		}
 	}
 }
@@ -157,47 +435,24 @@ pub struct SystemSummary {
 }
 
 #[derive(Clone, Debug, Deserialize)]
-pub(crate) struct ListSystemsResponse {
-	//total: u16,
-	//current_page: u8,
-	//size: u8,
-	//count: u8,
-	//items: CompactString,
-	pub systems: Vec<SystemResponse>
+pub(crate) struct RetrieveSystemIdResponse {
+	pub system_id: u32
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub(crate) struct LifetimeProductionResponse {
 	//system_id: u32,
 	start_date: NaiveDate,
-	production: Vec<u32>
-	//meta: Metadata
+	production: Vec<u32>,
+	meta: Metadata
 }
 
 #[derive(Clone, Debug, Deserialize)]
-pub(crate) struct MicroinverterProductionResponse {
+pub(crate) struct LifetimeConsumptionResponse {
 	//system_id: u32,
-	//granularity: Granularity,
-	//total_devices: u16,
-	//#[serde(with = "time::serde::iso8601")]
-	//start_date: DateTime<Utc>,
-	//#[serde(with = "time::serde::iso8601")]
-	//end_date: DateTime<Utc>,
-	//items: CompactString,
-	intervals: Vec<MicroinverterProduction>
-	//meta: Metadata
-}
-
-#[serde_as]
-#[derive(Clone, Debug, Deserialize)]
-pub struct MicroinverterProduction {
-	#[serde_as(as = "TimestampSeconds<i64>")]
-	pub end_at: DateTime<Utc>,
-	pub devices_reporting: u16,
-	#[serde(rename = "powr")]
-	pub instantaneous_power_watts: i32,
-	#[serde(rename = "enwh")]
-	pub energy_this_interval_wh: u32
+	start_date: NaiveDate,
+	consumption: Vec<u32>,
+	meta: Metadata
 }
 
 #[serde_as]
@@ -233,7 +488,7 @@ pub struct Metadata {
 	#[serde_as(as = "TimestampSeconds<i64>")]
 	pub last_energy_at: DateTime<Utc>,
 	#[serde_as(as = "Option<TimestampSeconds<i64>>")]
-	pub opertional_at: Option<DateTime<Utc>>
+	pub operational_at: Option<DateTime<Utc>>
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -242,3 +497,65 @@ pub struct Address {
 	pub state: CompactString,
 	pub postal_code: CompactString
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::cloud::mock::MockEnlighten;
+
+	#[test]
+	fn test_daily_across_dst() {
+		let days = daily(chrono_tz::America::Los_Angeles, NaiveDate::from_ymd_opt(2023, 3, 11).unwrap(), vec![1, 2, 3]);
+		let starts: Vec<_> = days.iter().map(|d| d.start.to_rfc3339()).collect();
+		assert_eq!(starts, ["2023-03-11T00:00:00-08:00", "2023-03-12T00:00:00-08:00", "2023-03-13T00:00:00-07:00"]);
+		assert_eq!(days[2].date, NaiveDate::from_ymd_opt(2023, 3, 13).unwrap());
+		assert_eq!(days[1].start.with_timezone(&Utc).to_rfc3339(), "2023-03-12T08:00:00+00:00");
+	}
+
+	#[test]
+	fn test_daily_midnight_dst_gap() {
+		// Chile springs forward at midnight.
+		let days = daily(chrono_tz::America::Santiago, NaiveDate::from_ymd_opt(2022, 9, 11).unwrap(), vec![1]);
+		assert_eq!(days[0].start.to_rfc3339(), "2022-09-11T01:00:00-03:00");
+	}
+
+	#[test]
+	fn test_daily_skipped_day() {
+		// Samoa skipped 2011-12-30 entirely when it crossed the date line.
+		let days = daily(chrono_tz::Pacific::Apia, NaiveDate::from_ymd_opt(2011, 12, 29).unwrap(), vec![1, 2, 3]);
+		assert_eq!(days[0].start.to_rfc3339(), "2011-12-29T00:00:00-10:00");
+		// The skipped day is kept, with the same start as the day after it.
+		assert_eq!(days[1].date, NaiveDate::from_ymd_opt(2011, 12, 30).unwrap());
+		assert_eq!(days[1].start.to_rfc3339(), "2011-12-31T00:00:00+14:00");
+		assert_eq!(days[1].watt_hours, 2);
+		assert_eq!(days[2].date, NaiveDate::from_ymd_opt(2011, 12, 31).unwrap());
+		assert_eq!(days[2].start, days[1].start);
+	}
+
+	#[tokio::test]
+	async fn test_mock_lifetime_production() {
+		let server = MockEnlighten::start().await.unwrap();
+		let production = server.system().await.get_lifetime_production(None, None, false).await.unwrap();
+		assert_eq!(production.days.len(), 11);
+		assert_eq!(production.days[0].start.to_rfc3339(), "2023-01-01T00:00:00-08:00");
+		assert_eq!(production.meta.operational_at.unwrap().timestamp(), 1557014400);
+	}
+
+	#[tokio::test]
+	async fn test_mock_lifetime_consumption() {
+		let server = MockEnlighten::start().await.unwrap();
+		let consumption = server.system().await.get_lifetime_consumption(None, None).await.unwrap();
+		assert_eq!(consumption.days.len(), 11);
+		assert_eq!(consumption.days[10].date, NaiveDate::from_ymd_opt(2023, 1, 11).unwrap());
+		assert_eq!(consumption.days[10].watt_hours, 12874);
+	}
+
+	#[tokio::test]
+	async fn test_mock_devices() {
+		let server = MockEnlighten::start().await.unwrap();
+		let system = server.system().await;
+		let devices = system.get_devices().await.unwrap();
+		let date = NaiveDate::from_ymd_opt(2023, 1, 11).unwrap();
+		assert_eq!(system.get_microinverter_telemetry(&devices.micros[0].serial_number, &date, None).await.unwrap().len(), 4);
+	}
+}
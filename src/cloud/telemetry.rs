@@ -0,0 +1,243 @@
+use chrono::DateTime;
+use chrono::Utc;
+use compact_str::CompactString;
+use serde::Deserialize;
+use serde_with::serde_as;
+use serde_with::TimestampSeconds;
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct TelemetryResponse<T> {
+	//system_id: u32,
+	//granularity: Granularity,
+	//total_devices: u16,
+	//#[serde(with = "time::serde::iso8601")]
+	//start_date: DateTime<Utc>,
+	//#[serde(with = "time::serde::iso8601")]
+	//end_date: DateTime<Utc>,
+	//items: CompactString,
+	pub intervals: Vec<T>
+	//meta: Metadata
+}
+
+/// The import and export endpoints group their intervals by day.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct GridTelemetryResponse {
+	//system_id: u32,
+	//start_date: NaiveDate,
+	//end_date: NaiveDate,
+	//total_devices: u16,
+	//items: CompactString,
+	pub intervals: Vec<Vec<GridInterval>>
+	//meta: Metadata
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct MicroinverterProduction {
+	#[serde_as(as = "TimestampSeconds<i64>")]
+	pub end_at: DateTime<Utc>,
+	pub devices_reporting: u16,
+	#[serde(rename = "powr")]
+	pub instantaneous_power_watts: i32,
+	#[serde(rename = "enwh")]
+	pub energy_this_interval_wh: u32
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct ConsumptionMeterInterval {
+	#[serde_as(as = "TimestampSeconds<i64>")]
+	pub end_at: DateTime<Utc>,
+	pub devices_reporting: u16,
+	#[serde(rename = "enwh")]
+	pub energy_this_interval_wh: u32
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct ProductionMeterInterval {
+	#[serde_as(as = "TimestampSeconds<i64>")]
+	pub end_at: DateTime<Utc>,
+	pub devices_reporting: u16,
+	#[serde(rename = "wh_del")]
+	pub energy_delivered_wh: u32
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct BatteryInterval {
+	#[serde_as(as = "TimestampSeconds<i64>")]
+	pub end_at: DateTime<Utc>,
+	pub charge: BatteryEnergy,
+	pub discharge: BatteryEnergy,
+	#[serde(rename = "soc")]
+	pub state_of_charge: StateOfCharge
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BatteryEnergy {
+	#[serde(rename = "enwh")]
+	pub energy_wh: u32,
+	pub devices_reporting: u16
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StateOfCharge {
+	/// `None` when no batteries reported during the interval.
+	pub percent: Option<u8>,
+	pub devices_reporting: u16
+}
+
+/// Energy imported from or exported to the grid, depending on which endpoint it came from.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct GridInterval {
+	#[serde_as(as = "TimestampSeconds<i64>")]
+	pub end_at: DateTime<Utc>,
+	#[serde(alias = "wh_imported", alias = "wh_exported")]
+	pub energy_wh: u32
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct LatestTelemetryResponse {
+	//system_id: u32,
+	pub devices: LatestTelemetry
+}
+
+/// The most recent power reading from each device, grouped by type.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct LatestTelemetry {
+	#[serde(default)]
+	pub meters: Vec<Reading>,
+	#[serde(default)]
+	pub micros: Vec<Reading>,
+	#[serde(default)]
+	pub encharges: Vec<BatteryReading>
+}
+
+impl LatestTelemetry {
+	#[inline]
+	pub fn meter(&self, name: &str) -> Option<&Reading> {
+		self.meters.iter().find(|m| m.name == name)
+	}
+
+	/// Site production, from the production meter if there is one, otherwise summed over the
+	/// microinverters.
+	pub fn production_watts(&self) -> i32 {
+		match self.meter("production") {
+			Some(meter) => meter.power_watts,
+			None => self.micros.iter().map(|m| m.power_watts).sum()
+		}
+	}
+
+	#[inline]
+	pub fn consumption_watts(&self) -> Option<i32> {
+		self.meter("consumption").map(|m| m.power_watts)
+	}
+
+	/// Positive when the batteries are discharging.
+	#[inline]
+	pub fn battery_watts(&self) -> i32 {
+		self.encharges.iter().map(|e| e.reading.power_watts).sum()
+	}
+
+	/// Mean state of charge of the batteries that reported one.
+	pub fn state_of_charge_percent(&self) -> Option<u8> {
+		let socs: Vec<u32> = self.encharges.iter().filter_map(|e| e.state_of_charge_percent).map(u32::from).collect();
+		match socs.is_empty() {
+			true => None,
+			false => Some((socs.iter().sum::<u32>() / socs.len() as u32) as u8)
+		}
+	}
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct Reading {
+	pub id: u64,
+	pub name: CompactString,
+	pub channel: Option<u8>,
+	#[serde_as(as = "TimestampSeconds<i64>")]
+	pub last_report_at: DateTime<Utc>,
+	#[serde(rename = "power")]
+	pub power_watts: i32
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BatteryReading {
+	#[serde(flatten)]
+	pub reading: Reading,
+	pub operational_mode: Option<CompactString>,
+	#[serde(rename = "soc")]
+	pub state_of_charge_percent: Option<u8>
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::NaiveDate;
+
+	use super::*;
+	use crate::cloud::mock::MockEnlighten;
+	use crate::cloud::Granularity;
+
+	#[test]
+	fn test_deserialize_battery() {
+		let response: TelemetryResponse<BatteryInterval> = serde_json::from_str(include_str!("mock/testdata/battery.json")).unwrap();
+		assert_eq!(response.intervals.len(), 6);
+		assert_eq!(response.intervals[0].discharge.energy_wh, 213);
+		assert_eq!(response.intervals[0].state_of_charge.percent, Some(31));
+		assert_eq!(response.intervals[4].state_of_charge.percent, None);
+		let net: Vec<_> = response.intervals.into_iter().map(|i| crate::EnergyInterval::from(i).watt_hours).collect();
+		assert_eq!(net, [213, 63, -77, -97, 0, -456]);
+	}
+
+	#[test]
+	fn test_deserialize_grid() {
+		let imports: GridTelemetryResponse = serde_json::from_str(include_str!("mock/testdata/energy_import_telemetry.json")).unwrap();
+		let exports: GridTelemetryResponse = serde_json::from_str(include_str!("mock/testdata/energy_export_telemetry.json")).unwrap();
+		assert_eq!(imports.intervals.iter().flatten().map(|i| i.energy_wh).sum::<u32>(), 277);
+		assert_eq!(exports.intervals.iter().flatten().map(|i| i.energy_wh).sum::<u32>(), 176);
+	}
+
+	#[test]
+	fn test_deserialize_latest_telemetry() {
+		let response: LatestTelemetryResponse = serde_json::from_str(include_str!("mock/testdata/latest_telemetry.json")).unwrap();
+		let latest = response.devices;
+		assert_eq!(latest.production_watts(), 3107);
+		assert_eq!(latest.micros.iter().map(|m| m.power_watts).sum::<i32>(), 3107);
+		assert_eq!(latest.consumption_watts(), Some(1842));
+		assert_eq!(latest.battery_watts(), -1200);
+		assert_eq!(latest.state_of_charge_percent(), Some(77));
+		let aggregate = crate::AggregateProduction::from(latest);
+		assert_eq!(aggregate.inverters_reporting, 2);
+		assert_eq!(aggregate.instantaneous_power_watts, 3107);
+		assert_eq!(aggregate.timestamp.timestamp(), 1673470500);
+	}
+
+	#[tokio::test]
+	async fn test_mock_consumption_meter_telemetry() {
+		let server = MockEnlighten::start().await.unwrap();
+		let date = NaiveDate::from_ymd_opt(2023, 1, 11).unwrap();
+		let intervals = server.system().await.get_consumption_meter_telemetry(&date, Some(Granularity::Day)).await.unwrap();
+		assert_eq!(intervals.iter().map(|i| i.energy_this_interval_wh).sum::<u32>(), 1533);
+	}
+
+	#[tokio::test]
+	async fn test_mock_meter_battery_and_grid_telemetry() {
+		let server = MockEnlighten::start().await.unwrap();
+		let system = server.system().await;
+		let date = NaiveDate::from_ymd_opt(2023, 1, 11).unwrap();
+		assert_eq!(system.get_production_meter_telemetry(&date, None).await.unwrap().len(), 6);
+		assert_eq!(system.get_battery_telemetry(&date, None).await.unwrap().len(), 6);
+		assert_eq!(system.get_energy_import_telemetry(&date, None).await.unwrap().len(), 4);
+		assert_eq!(system.get_energy_export_telemetry(&date, None).await.unwrap().len(), 4);
+	}
+
+	#[tokio::test]
+	async fn test_mock_latest_telemetry() {
+		let server = MockEnlighten::start().await.unwrap();
+		let system = server.system().await;
+		let production = crate::AggregateProduction::from(system.get_latest_telemetry().await.unwrap());
+		assert_eq!(production.instantaneous_power_watts as u32, system.get_summary().await.unwrap().current_power);
+	}
+}
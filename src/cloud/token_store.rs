@@ -0,0 +1,130 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use super::Tokens;
+
+/// Persists OAuth tokens across restarts.  Enlighten rotates the refresh token on every
+/// refresh, so the client saves to its store each time it obtains new tokens.
+pub trait TokenStore: Send + Sync {
+	fn load(&self) -> io::Result<Option<Tokens>>;
+	fn save(&self, tokens: &Tokens) -> io::Result<()>;
+}
+
+/// Stores tokens as JSON.  Writes go to a temporary file in the same directory, which is then
+/// renamed over the original, so a crash mid-write never leaves a truncated file behind.
+#[derive(Clone, Debug)]
+pub struct FileTokenStore {
+	path: PathBuf
+}
+
+impl FileTokenStore {
+	#[inline]
+	pub fn new(path: impl Into<PathBuf>) -> Self {
+		Self { path: path.into() }
+	}
+}
+
+impl TokenStore for FileTokenStore {
+	fn load(&self) -> io::Result<Option<Tokens>> {
+		match fs::read(&self.path) {
+			Ok(data) => Ok(Some(serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?)),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+			Err(e) => Err(e)
+		}
+	}
+
+	#[inline]
+	fn save(&self, tokens: &Tokens) -> io::Result<()> {
+		write_atomically(&self.path, &serde_json::to_vec(tokens)?)
+	}
+}
+
+/// Writes `data` to a temporary file next to `path`, readable only by the owner, then renames
+/// it over `path`.
+pub(crate) fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+	let mut tmp_path = path.to_path_buf().into_os_string();
+	tmp_path.push(".tmp");
+	let mut options = fs::OpenOptions::new();
+	options.write(true).create(true).truncate(true);
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::OpenOptionsExt;
+		options.mode(0o600);
+	}
+	let mut file = options.open(&tmp_path)?;
+	file.write_all(data)?;
+	file.sync_all()?;
+	fs::rename(&tmp_path, path)
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+	tokens: Mutex<Option<Tokens>>
+}
+
+impl MemoryTokenStore {
+	#[inline]
+	pub fn new(tokens: Option<Tokens>) -> Self {
+		Self { tokens: Mutex::new(tokens) }
+	}
+}
+
+impl TokenStore for MemoryTokenStore {
+	#[inline]
+	fn load(&self) -> io::Result<Option<Tokens>> {
+		Ok(self.tokens.lock().unwrap().clone())
+	}
+
+	#[inline]
+	fn save(&self, tokens: &Tokens) -> io::Result<()> {
+		*self.tokens.lock().unwrap() = Some(tokens.clone());
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+
+	use chrono::TimeZone;
+	use chrono::Utc;
+
+	use super::*;
+
+	fn tokens() -> Tokens {
+		Tokens::new("access", "refresh", Some(Utc.timestamp_opt(1671053563, 0).unwrap()))
+	}
+
+	#[test]
+	fn test_memory_token_store() {
+		let store = MemoryTokenStore::default();
+		assert_eq!(store.load().unwrap(), None);
+		store.save(&tokens()).unwrap();
+		assert_eq!(store.load().unwrap(), Some(tokens()));
+	}
+
+	#[test]
+	fn test_file_token_store() {
+		let path = env::temp_dir().join(format!("enphase-test-token-store-{}.json", std::process::id()));
+		let store = FileTokenStore::new(&path);
+		assert_eq!(store.load().unwrap(), None);
+		store.save(&tokens()).unwrap();
+		assert_eq!(store.load().unwrap(), Some(tokens()));
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+		}
+		fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_deserialize_tokens_without_expiry() {
+		let tokens: Tokens = serde_json::from_str(r#"{"access": "a", "refresh": "r"}"#).unwrap();
+		assert_eq!(tokens, Tokens::new("a", "r", None));
+	}
+}
@@ -0,0 +1,241 @@
+//! Sources for the secrets used to authenticate against an Envoy (digest auth username and
+//! password) or the Enlighten API (OAuth client ID and secret).  Clients re-query their provider
+//! when authentication fails, so rotated secrets are picked up without rebuilding the client.
+
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+
+use compact_str::CompactString;
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct Credentials {
+	pub username: CompactString,
+	pub password: CompactString
+}
+
+impl Credentials {
+	#[inline]
+	pub fn new(username: impl AsRef<str>, password: impl AsRef<str>) -> Self {
+		Self {
+			username: username.as_ref().into(),
+			password: password.as_ref().into()
+		}
+	}
+}
+
+impl fmt::Debug for Credentials {
+	#[inline]
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Credentials").field("username", &self.username).field("password", &"<redacted>").finish()
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CredentialError {
+	#[error("Environment variable {0}: {1}")]
+	Env(CompactString, env::VarError),
+	#[error("I/O error: {0}")]
+	Io(#[from] io::Error),
+	#[error("{0} is accessible by group or other users (mode {1:o})")]
+	InsecurePermissions(PathBuf, u32),
+	#[error("Credential command exited with {0}")]
+	Command(std::process::ExitStatus),
+	#[error("Credential output is not valid UTF-8")]
+	Utf8
+}
+
+/// Implementations may block (reading files, running commands); clients re-query them on Tokio's
+/// blocking thread pool rather than on the async runtime.
+pub trait CredentialProvider: Send + Sync {
+	fn credentials(&self) -> Result<Credentials, CredentialError>;
+}
+
+/// Queries `provider` on a blocking thread so that slow providers don't stall the runtime.
+pub(crate) async fn query(provider: &Arc<dyn CredentialProvider>) -> Result<Credentials, CredentialError> {
+	let provider = provider.clone();
+	crate::unblock(move || provider.credentials()).await
+}
+
+impl CredentialProvider for Credentials {
+	#[inline]
+	fn credentials(&self) -> Result<Credentials, CredentialError> {
+		Ok(self.clone())
+	}
+}
+
+/// Reads the username and password from environment variables each time it is queried.
+#[derive(Clone, Debug)]
+pub struct EnvProvider {
+	username_var: CompactString,
+	password_var: CompactString
+}
+
+impl EnvProvider {
+	#[inline]
+	pub fn new(username_var: impl AsRef<str>, password_var: impl AsRef<str>) -> Self {
+		Self {
+			username_var: username_var.as_ref().into(),
+			password_var: password_var.as_ref().into()
+		}
+	}
+
+	#[inline]
+	fn var(name: &CompactString) -> Result<String, CredentialError> {
+		env::var(name.as_str()).map_err(|e| CredentialError::Env(name.clone(), e))
+	}
+}
+
+impl CredentialProvider for EnvProvider {
+	#[inline]
+	fn credentials(&self) -> Result<Credentials, CredentialError> {
+		Ok(Credentials::new(Self::var(&self.username_var)?, Self::var(&self.password_var)?))
+	}
+}
+
+/// Reads the password from a file, ignoring trailing whitespace.  On Unix, the file is rejected
+/// if it is readable or writable by anyone other than its owner.
+#[derive(Clone, Debug)]
+pub struct FileProvider {
+	username: CompactString,
+	path: PathBuf
+}
+
+impl FileProvider {
+	#[inline]
+	pub fn new(username: impl AsRef<str>, path: impl Into<PathBuf>) -> Self {
+		Self { username: username.as_ref().into(), path: path.into() }
+	}
+
+	#[cfg(unix)]
+	fn check_permissions(&self) -> Result<(), CredentialError> {
+		use std::os::unix::fs::PermissionsExt;
+		let mode = fs::metadata(&self.path)?.permissions().mode();
+		if (mode & 0o077 != 0) {
+			return Err(CredentialError::InsecurePermissions(self.path.clone(), mode & 0o777));
+		}
+		Ok(())
+	}
+
+	#[cfg(not(unix))]
+	#[inline]
+	fn check_permissions(&self) -> Result<(), CredentialError> {
+		Ok(())
+	}
+}
+
+impl CredentialProvider for FileProvider {
+	fn credentials(&self) -> Result<Credentials, CredentialError> {
+		self.check_permissions()?;
+		let password = fs::read_to_string(&self.path)?;
+		Ok(Credentials::new(&self.username, password.trim_end()))
+	}
+}
+
+/// Runs an external command, such as a password manager CLI, and uses its standard output as
+/// the password.
+#[derive(Clone, Debug)]
+pub struct CommandProvider {
+	username: CompactString,
+	program: OsString,
+	args: Vec<OsString>
+}
+
+impl CommandProvider {
+	#[inline]
+	pub fn new(username: impl AsRef<str>, program: impl Into<OsString>, args: impl IntoIterator<Item = impl Into<OsString>>) -> Self {
+		Self {
+			username: username.as_ref().into(),
+			program: program.into(),
+			args: args.into_iter().map(Into::into).collect()
+		}
+	}
+
+	/// Runs `command` through `sh -c`, for commands supplied as a single string.
+	#[inline]
+	pub fn shell(username: impl AsRef<str>, command: impl Into<OsString>) -> Self {
+		Self::new(username, "sh", [OsString::from("-c"), command.into()])
+	}
+}
+
+impl CredentialProvider for CommandProvider {
+	fn credentials(&self) -> Result<Credentials, CredentialError> {
+		let output = Command::new(&self.program).args(&self.args).output()?;
+		if (!output.status.success()) {
+			return Err(CredentialError::Command(output.status));
+		}
+		let password = String::from_utf8(output.stdout).map_err(|_| CredentialError::Utf8)?;
+		Ok(Credentials::new(&self.username, password.trim_end()))
+	}
+}
+
+/// Hands out each of a list of credentials in turn, then keeps repeating the last, to stand in
+/// for a secret that's rotated between queries.
+#[cfg(test)]
+pub(crate) struct Rotating(std::sync::Mutex<std::collections::VecDeque<Credentials>>);
+
+#[cfg(test)]
+impl Rotating {
+	pub(crate) fn new(credentials: impl IntoIterator<Item = Credentials>) -> Arc<Self> {
+		Arc::new(Self(std::sync::Mutex::new(credentials.into_iter().collect())))
+	}
+}
+
+#[cfg(test)]
+impl CredentialProvider for Rotating {
+	fn credentials(&self) -> Result<Credentials, CredentialError> {
+		let mut remaining = self.0.lock().unwrap();
+		// Tests always supply at least one set.
+		match remaining.len() {
+			1 => Ok(remaining[0].clone()),
+			_ => Ok(remaining.pop_front().unwrap())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_credentials_debug_redacts_password() {
+		let credentials = Credentials::new("installer", "hunter2");
+		assert!(!format!("{credentials:?}").contains("hunter2"));
+	}
+
+	#[test]
+	fn test_env_provider() {
+		env::set_var("ENPHASE_TEST_ENV_PROVIDER_USERNAME", "envoy");
+		env::set_var("ENPHASE_TEST_ENV_PROVIDER_PASSWORD", "008901");
+		let provider = EnvProvider::new("ENPHASE_TEST_ENV_PROVIDER_USERNAME", "ENPHASE_TEST_ENV_PROVIDER_PASSWORD");
+		assert_eq!(provider.credentials().unwrap(), Credentials::new("envoy", "008901"));
+		assert!(matches!(EnvProvider::new("ENPHASE_TEST_ENV_PROVIDER_USERNAME", "ENPHASE_TEST_ENV_PROVIDER_MISSING").credentials(), Err(CredentialError::Env(..))));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_file_provider() {
+		use std::os::unix::fs::PermissionsExt;
+
+		let path = env::temp_dir().join(format!("enphase-test-file-provider-{}", std::process::id()));
+		fs::write(&path, "s3cret\n").unwrap();
+		fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+		let provider = FileProvider::new("installer", &path);
+		assert!(matches!(provider.credentials(), Err(CredentialError::InsecurePermissions(_, 0o644))));
+		fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+		assert_eq!(provider.credentials().unwrap(), Credentials::new("installer", "s3cret"));
+		fs::remove_file(&path).unwrap();
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_command_provider() {
+		assert_eq!(CommandProvider::shell("installer", "echo s3cret").credentials().unwrap(), Credentials::new("installer", "s3cret"));
+		assert!(matches!(CommandProvider::shell("installer", "exit 3").credentials(), Err(CredentialError::Command(_))));
+	}
+}
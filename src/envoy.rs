@@ -1,7 +1,30 @@
-use compact_str::CompactString;
+#[cfg(feature = "vcr")] use std::io;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use chrono::DateTime;
+use chrono::Utc;
+#[cfg(feature = "clap")] use compact_str::CompactString;
 use diqwest::WithDigestAuth;
+use reqwest::StatusCode;
 use url::Url;
 
+use crate::credentials::CredentialError;
+use crate::credentials::CredentialProvider;
+use crate::credentials::Credentials;
+
+#[cfg(feature = "control")] mod comm_check;
+#[cfg(feature = "control")] use comm_check::CommCheckResponse;
+#[cfg(feature = "control")] use comm_check::CommCheckStatus;
+#[cfg(feature = "control")] pub use comm_check::CommLevel;
+pub mod credentials;
+mod date_time;
+pub use date_time::*;
+#[cfg(feature = "discovery")] mod discovery;
+#[cfg(feature = "discovery")] pub use discovery::*;
+mod events;
+use events::EventPage;
+pub use events::*;
 mod home;
 pub use home::*;
 mod info;
@@ -10,8 +33,14 @@ mod inventory;
 pub use inventory::*;
 mod inverters;
 pub use inverters::*;
+#[cfg(feature = "legacy")] pub mod legacy;
+#[cfg(any(test, feature = "mock"))] pub mod mock;
+mod network_config;
+pub use network_config::*;
 mod production;
 pub use production::*;
+mod wireless;
+pub use wireless::*;
 
 #[cfg(feature = "clap")]
 #[derive(Debug, clap::Parser)]
@@ -20,23 +49,45 @@ pub struct EnvoyConfig {
 	envoy_base_url: CompactString,
 	#[clap(long, env = "ENVOY_USERNAME")]
 	envoy_username: CompactString,
-	#[clap(long, env = "ENVOY_PASSWORD")]
-	envoy_password: CompactString
+	#[clap(long, env = "ENVOY_PASSWORD", required_unless_present_any = ["envoy_password_file", "envoy_password_command"])]
+	envoy_password: Option<CompactString>,
+	#[clap(long, env = "ENVOY_PASSWORD_FILE", conflicts_with_all = ["envoy_password", "envoy_password_command"])]
+	envoy_password_file: Option<std::path::PathBuf>,
+	#[clap(long, env = "ENVOY_PASSWORD_COMMAND", conflicts_with_all = ["envoy_password", "envoy_password_file"])]
+	envoy_password_command: Option<String>
 }
 
 #[cfg(feature = "clap")]
 impl EnvoyConfig {
 	#[inline]
-	pub fn client(&self) -> Result<Client, url::ParseError> {
-		Client::new(&self.envoy_base_url, &self.envoy_username, &self.envoy_password)
+	pub fn client(&self) -> Result<Client, ClientError> {
+		match (&self.envoy_password, &self.envoy_password_file, &self.envoy_password_command) {
+			(_, Some(path), _) => Client::with_credential_provider(&self.envoy_base_url, Arc::new(crate::credentials::FileProvider::new(&self.envoy_username, path))),
+			(_, _, Some(command)) => Client::with_credential_provider(&self.envoy_base_url, Arc::new(crate::credentials::CommandProvider::shell(&self.envoy_username, command))),
+			(password, None, None) => Ok(Client::new(&self.envoy_base_url, &self.envoy_username, password.as_deref().unwrap_or_default())?)
+		}
 	}
 }
 
+const EVENT_PAGE_SIZE: u32 = 100;
+#[cfg(feature = "control")]
+const COMM_CHECK_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+#[cfg(feature = "control")]
+const COMM_CHECK_MAX_POLLS: u32 = 60;
+
 pub struct Client {
 	client: reqwest::Client,
 	base_url: Url,
-	username: CompactString,
-	password: CompactString
+	credential_provider: Arc<dyn CredentialProvider>,
+	credentials: RwLock<Credentials>
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+	#[error("Invalid base URL: {0}")]
+	Url(#[from] url::ParseError),
+	#[error("Failed to retrieve credentials: {0}")]
+	Credentials(#[from] CredentialError)
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -47,8 +98,33 @@ pub enum InfoError {
 	Xml(#[from] serde_xml_rs::Error)
 }
 
+#[cfg(feature = "control")]
+#[derive(Debug, thiserror::Error)]
+pub enum CommCheckError {
+	#[error("HTTP error: {0}")]
+	Http(#[from] diqwest::error::Error),
+	#[error("Communication check did not complete after {0} polls")]
+	Timeout(u32),
+	/// The Envoy ended the check with a status other than complete; `levels` holds whatever
+	/// results it reported.
+	#[error("Communication check ended with status \"{status}\"")]
+	Failed { status: String, levels: Vec<CommLevel> }
+}
+
 impl Client {
 	pub fn new(base_url: impl AsRef<str>, username: impl AsRef<str>, password: impl AsRef<str>) -> Result<Self, url::ParseError> {
+		let credentials = Credentials::new(username, password);
+		Self::build(base_url, Arc::new(credentials.clone()), credentials)
+	}
+
+	/// Creates a client whose digest auth credentials come from `provider`.  The provider is
+	/// queried once up front, and again whenever the Envoy rejects the cached credentials.
+	pub fn with_credential_provider(base_url: impl AsRef<str>, provider: Arc<dyn CredentialProvider>) -> Result<Self, ClientError> {
+		let credentials = provider.credentials()?;
+		Ok(Self::build(base_url, provider, credentials)?)
+	}
+
+	fn build(base_url: impl AsRef<str>, credential_provider: Arc<dyn CredentialProvider>, credentials: Credentials) -> Result<Self, url::ParseError> {
 		let mut base_url = base_url.as_ref().to_owned();
 		if (!base_url.ends_with('/')) {
 			base_url.push('/');
@@ -56,8 +132,8 @@ impl Client {
 		Ok(Self {
 			base_url: Url::parse(&base_url)?,
 			client: reqwest::Client::new(),
-			username: username.as_ref().into(),
-			password: password.as_ref().into()
+			credential_provider,
+			credentials: RwLock::new(credentials)
 		})
 	}
 
@@ -66,6 +142,68 @@ impl Client {
 		&self.base_url
 	}
 
+	/// Points this client at a [`vcr::Recorder`](crate::vcr::Recorder) forwarding to its current
+	/// base URL, or at a [`vcr::Player`](crate::vcr::Player) replaying a cassette.  Keep the
+	/// returned [`Vcr`](crate::vcr::Vcr) alive while the client is in use.
+	#[cfg(feature = "vcr")]
+	pub async fn with_vcr(self, mode: crate::vcr::Mode) -> io::Result<(Self, crate::vcr::Vcr)> {
+		let vcr = crate::vcr::Vcr::start(mode, self.base_url.as_str()).await?;
+		// The local server's URL always parses.
+		let base_url = Url::parse(&format!("{}/", vcr.base_url())).unwrap();
+		Ok((Self { base_url, ..self }, vcr))
+	}
+
+	async fn send_with_credentials(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, diqwest::error::Error> {
+		let credentials = self.credentials.read().unwrap().clone();
+		let retry = request.try_clone();
+		let response = request.send_with_digest_auth(&credentials.username, &credentials.password).await?;
+		if (response.status() != StatusCode::UNAUTHORIZED) {
+			return Ok(response);
+		}
+		// The secret may have been rotated; if the provider fails or has nothing new, the
+		// original 401 is the most useful thing to surface.
+		let Some(retry) = retry else {
+			return Ok(response);
+		};
+		match crate::credentials::query(&self.credential_provider).await {
+			Ok(fresh) if fresh != credentials => {
+				*self.credentials.write().unwrap() = fresh.clone();
+				retry.send_with_digest_auth(&fresh.username, &fresh.password).await
+			},
+			_ => Ok(response)
+		}
+	}
+
+	pub async fn date_time(&self) -> Result<DateTimeConfig, diqwest::error::Error> {
+		let url = self.base_url.join("admin/lib/date_time_display.json").unwrap();
+		let response = self.send_with_credentials(self.client.get(url)).await?.error_for_status()?.json().await?;
+		Ok(response)
+	}
+
+	/// Retrieves all event log entries logged at or after `since`, newest first.
+	pub async fn events(&self, since: DateTime<Utc>) -> Result<Vec<Event>, diqwest::error::Error> {
+		let mut result = Vec::new();
+		let mut start = 0;
+		loop {
+			let url = self.base_url.join(&format!("datatab/event_dt.rb?start={start}&length={EVENT_PAGE_SIZE}")).unwrap();
+			let page: EventPage = self.send_with_credentials(self.client.get(url)).await?.error_for_status()?.json().await?;
+			let count = page.events.len() as u32;
+			let mut reached_since = false;
+			for event in page.events {
+				if (event.timestamp < since) {
+					reached_since = true;
+					break;
+				}
+				result.push(event);
+			}
+			start += count;
+			if (reached_since || count == 0 || start >= page.total_records) {
+				break;
+			}
+		}
+		Ok(result)
+	}
+
 	pub async fn home(&self) -> Result<Home, reqwest::Error> {
 		let url = self.base_url.join("home.json").unwrap();
 		self.client.get(url).send().await?.error_for_status()?.json().await
@@ -84,14 +222,91 @@ impl Client {
 
 	pub async fn inverters(&self) -> Result<Vec<Inverter>, diqwest::error::Error> {
 		let url = self.base_url.join("api/v1/production/inverters").unwrap();
-		let response = self.client.get(url).send_with_digest_auth(&self.username, &self.password).await?.error_for_status()?.json().await?;
+		let response = self.send_with_credentials(self.client.get(url)).await?.error_for_status()?.json().await?;
 		Ok(response)
 	}
 
+	/// Scrapes the production summary from a legacy Envoy-C that lacks `production.json`.
+	#[cfg(feature = "legacy")]
+	pub async fn legacy_summary(&self) -> Result<Summary, legacy::LegacyError> {
+		let production = self.client.get(self.base_url.join("production?locale=en").unwrap()).send().await?.error_for_status()?.text().await?;
+		let home = self.client.get(self.base_url.join("home?locale=en").unwrap()).send().await?.error_for_status()?.text().await?;
+		legacy::parse_summary(&production, &home, Utc::now())
+	}
+
+	/// Scrapes per-microinverter readings from a legacy Envoy-C that lacks
+	/// `api/v1/production/inverters`.  `utc_offset` is the Envoy's local UTC offset.
+	#[cfg(feature = "legacy")]
+	pub async fn legacy_inverters(&self, utc_offset: chrono::FixedOffset) -> Result<Vec<Inverter>, legacy::LegacyError> {
+		let html = self.client.get(self.base_url.join("inverters?locale=en").unwrap()).send().await?.error_for_status()?.text().await?;
+		legacy::parse_inverters(&html, utc_offset)
+	}
+
+	pub async fn network_config(&self) -> Result<NetworkConfig, diqwest::error::Error> {
+		let url = self.base_url.join("admin/lib/network_display.json").unwrap();
+		let response = self.send_with_credentials(self.client.get(url)).await?.error_for_status()?.json().await?;
+		Ok(response)
+	}
+
+	/// Starts an installer "PCU comm check" and polls until it completes, returning the
+	/// communication level (0-5) reported for each microinverter serial number.  Any status
+	/// other than idle, running or complete ends the check with [`CommCheckError::Failed`].
+	#[cfg(feature = "control")]
+	pub async fn pcu_comm_check(&self) -> Result<Vec<CommLevel>, CommCheckError> {
+		let url = self.base_url.join("installer/pcu_comm_check").unwrap();
+		self.send_with_credentials(self.client.post(url.clone()))
+			.await?
+			.error_for_status()
+			.map_err(diqwest::error::Error::from)?;
+		for _ in 0..COMM_CHECK_MAX_POLLS {
+			tokio::time::sleep(COMM_CHECK_POLL_INTERVAL).await;
+			let response: CommCheckResponse = self
+				.send_with_credentials(self.client.get(url.clone()))
+				.await?
+				.error_for_status()
+				.map_err(diqwest::error::Error::from)?
+				.json()
+				.await
+				.map_err(diqwest::error::Error::from)?;
+			match &response.status {
+				CommCheckStatus::Complete => return Ok(response.into_levels()),
+				CommCheckStatus::Idle | CommCheckStatus::Running => (),
+				CommCheckStatus::Other(status) => {
+					let status = status.clone();
+					return Err(CommCheckError::Failed { status, levels: response.into_levels() });
+				}
+			}
+		}
+		Err(CommCheckError::Timeout(COMM_CHECK_MAX_POLLS))
+	}
+
 	pub async fn production(&self) -> Result<EnergyStats, reqwest::Error> {
 		let url = self.base_url.join("production.json?details=1").unwrap();
 		self.client.get(url).send().await?.error_for_status()?.json().await
 	}
+
+	/// Like [`Client::production`], but without the per-line breakdown, which is considerably
+	/// cheaper for the Envoy to generate.
+	pub async fn production_summary(&self) -> Result<EnergyStats, reqwest::Error> {
+		let url = self.base_url.join("production.json").unwrap();
+		self.client.get(url).send().await?.error_for_status()?.json().await
+	}
+
+	pub async fn simple_consumption(&self) -> Result<EnergyTotals, reqwest::Error> {
+		let url = self.base_url.join("api/v1/consumption").unwrap();
+		self.client.get(url).send().await?.error_for_status()?.json().await
+	}
+
+	pub async fn simple_production(&self) -> Result<EnergyTotals, reqwest::Error> {
+		let url = self.base_url.join("api/v1/production").unwrap();
+		self.client.get(url).send().await?.error_for_status()?.json().await
+	}
+
+	pub async fn wireless(&self) -> Result<Wireless, diqwest::error::Error> {
+		let url = self.base_url.join("admin/lib/wireless_display.json?site_info=0").unwrap();
+		let response = self.send_with_credentials(self.client.get(url)).await?.error_for_status()?.json().await?;
+		Ok(response)
+	}
 }
 
 #[cfg(test)]
@@ -102,6 +317,20 @@ mod test {
 		Client::new(std::env::var("ENVOY_URL").unwrap(), std::env::var("ENVOY_USERNAME").unwrap_or("".into()), std::env::var("ENVOY_PASSWORD").unwrap_or("".into())).unwrap()
 	}
 
+	#[tokio::test]
+	#[cfg_attr(not(all(envoy_tests, envoy_auth_tests)), ignore)]
+	async fn test_date_time() {
+		let client = client();
+		client.date_time().await.unwrap();
+	}
+
+	#[tokio::test]
+	#[cfg_attr(not(all(envoy_tests, envoy_auth_tests)), ignore)]
+	async fn test_events() {
+		let client = client();
+		client.events(DateTime::<Utc>::MIN_UTC).await.unwrap();
+	}
+
 	#[tokio::test]
 	#[cfg_attr(not(envoy_tests), ignore)]
 	async fn test_home() {
@@ -130,10 +359,189 @@ mod test {
 		client.inverters().await.unwrap();
 	}
 
+	#[tokio::test]
+	#[cfg_attr(not(all(envoy_tests, envoy_auth_tests)), ignore)]
+	async fn test_network_config() {
+		let client = client();
+		client.network_config().await.unwrap();
+	}
+
 	#[tokio::test]
 	#[cfg_attr(not(envoy_tests), ignore)]
 	async fn test_production() {
 		let client = client();
 		client.production().await.unwrap();
 	}
+
+	#[tokio::test]
+	#[cfg_attr(not(envoy_tests), ignore)]
+	async fn test_production_summary() {
+		let client = client();
+		client.production_summary().await.unwrap();
+	}
+
+	#[tokio::test]
+	#[cfg_attr(not(envoy_tests), ignore)]
+	async fn test_simple_consumption() {
+		let client = client();
+		client.simple_consumption().await.unwrap();
+	}
+
+	#[tokio::test]
+	#[cfg_attr(not(envoy_tests), ignore)]
+	async fn test_simple_production() {
+		let client = client();
+		client.simple_production().await.unwrap();
+	}
+
+	#[tokio::test]
+	#[cfg_attr(not(all(envoy_tests, envoy_auth_tests)), ignore)]
+	async fn test_wireless() {
+		let client = client();
+		client.wireless().await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_mock_endpoints() {
+		let server = mock::MockEnvoy::start().await.unwrap();
+		let client = server.client();
+		client.home().await.unwrap();
+		client.info().await.unwrap();
+		client.inventory().await.unwrap();
+		client.production().await.unwrap();
+		client.production_summary().await.unwrap();
+		client.simple_consumption().await.unwrap();
+		client.simple_production().await.unwrap();
+		assert!(!client.inverters().await.unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_mock_admin_endpoints() {
+		let server = mock::MockEnvoy::start().await.unwrap();
+		let client = server.client();
+		client.date_time().await.unwrap();
+		assert_eq!(client.network_config().await.unwrap().interfaces.len(), 2);
+		client.wireless().await.unwrap();
+		assert_eq!(client.events(DateTime::<Utc>::MIN_UTC).await.unwrap().len(), 5);
+
+		let client = Client::new(server.base_url(), mock::USERNAME, "wrong").unwrap();
+		client.date_time().await.unwrap_err();
+		client.network_config().await.unwrap_err();
+		client.wireless().await.unwrap_err();
+		client.events(DateTime::<Utc>::MIN_UTC).await.unwrap_err();
+	}
+
+	#[tokio::test]
+	async fn test_mock_events_pagination() {
+		let server = mock::MockEnvoy::start().await.unwrap();
+		let mut page: serde_json::Value = serde_json::from_str(include_str!("envoy/events/testdata/event_dt.json")).unwrap();
+		let rows = page["aaData"].as_array().unwrap().clone();
+		// The Envoy may return fewer rows than asked for, so the client keeps going until it has
+		// every record.
+		page["aaData"] = rows[..3].into();
+		server.set_fixture(format!("/datatab/event_dt.rb?start=0&length={EVENT_PAGE_SIZE}"), true, page.to_string());
+		page["aaData"] = rows[3..].into();
+		page["aaData"][1][4] = "alert".into();
+		server.set_fixture(format!("/datatab/event_dt.rb?start=3&length={EVENT_PAGE_SIZE}"), true, page.to_string());
+		let client = server.client();
+
+		let events = client.events(DateTime::<Utc>::MIN_UTC).await.unwrap();
+		assert_eq!(events.iter().map(|e| e.id).collect::<Vec<_>>(), [1875, 1874, 1873, 1872, 1871]);
+		assert_eq!(events[4].severity, Severity::Other("alert".into()));
+		let since = DateTime::from_timestamp(1671040211, 0).unwrap();
+		assert_eq!(client.events(since).await.unwrap().len(), 3);
+	}
+
+	#[cfg(feature = "control")]
+	#[tokio::test(start_paused = true)]
+	async fn test_mock_pcu_comm_check() {
+		let server = mock::MockEnvoy::start().await.unwrap();
+		let client = server.client();
+		let start = tokio::time::Instant::now();
+		let levels = client.pcu_comm_check().await.unwrap();
+		// One poll finds the check still running, the next finds it complete.
+		assert_eq!(start.elapsed(), COMM_CHECK_POLL_INTERVAL * 2);
+		assert_eq!(levels.len(), 3);
+		assert_eq!(levels[0], CommLevel { serial_num: "121816047176".into(), level: 5 });
+	}
+
+	#[cfg(feature = "control")]
+	#[tokio::test(start_paused = true)]
+	async fn test_mock_pcu_comm_check_failed() {
+		let server = mock::MockEnvoy::start().await.unwrap();
+		server.set_fixture("/installer/pcu_comm_check", true, include_str!("envoy/comm_check/testdata/failed.json"));
+		let client = server.client();
+		match client.pcu_comm_check().await {
+			Err(CommCheckError::Failed { status, levels }) => {
+				assert_eq!(status, "failed");
+				assert_eq!(levels.len(), 1);
+			},
+			result => panic!("Unexpected result {result:?}")
+		}
+
+		server.inject("/installer/pcu_comm_check", mock::Fault::Status(503));
+		assert!(matches!(client.pcu_comm_check().await, Err(CommCheckError::Http(_))));
+	}
+
+	#[tokio::test]
+	async fn test_mock_wrong_password() {
+		let server = mock::MockEnvoy::start().await.unwrap();
+		let client = Client::new(server.base_url(), mock::USERNAME, "wrong").unwrap();
+		client.home().await.unwrap();
+		client.inverters().await.unwrap_err();
+	}
+
+	#[tokio::test]
+	async fn test_mock_rotated_password() {
+		let server = mock::MockEnvoy::start().await.unwrap();
+		let provider = crate::credentials::Rotating::new([Credentials::new(mock::USERNAME, "old"), Credentials::new(mock::USERNAME, mock::PASSWORD)]);
+		let client = Client::with_credential_provider(server.base_url(), provider).unwrap();
+		client.inverters().await.unwrap();
+		// Challenge and rejection with the old password, then challenge and success with the new.
+		assert_eq!(server.requests(), 4);
+		client.inverters().await.unwrap();
+		assert_eq!(server.requests(), 6);
+	}
+
+	#[tokio::test]
+	async fn test_mock_unrotated_password() {
+		let server = mock::MockEnvoy::start().await.unwrap();
+		let provider = crate::credentials::Rotating::new([Credentials::new(mock::USERNAME, "wrong")]);
+		let client = Client::with_credential_provider(server.base_url(), provider).unwrap();
+		let response = client
+			.send_with_credentials(client.client.get(client.base_url.join("api/v1/production/inverters").unwrap()))
+			.await
+			.unwrap();
+		assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+		// No retry when the provider has nothing new.
+		assert_eq!(server.requests(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_mock_faults() {
+		let server = mock::MockEnvoy::start().await.unwrap();
+		let client = server.client();
+		server.inject_once("/home.json", mock::Fault::Status(503));
+		server.inject_once("/home.json", mock::Fault::Truncated);
+		server.inject("/production.json?details=1", mock::Fault::Body("{}".into()));
+		assert_eq!(client.home().await.unwrap_err().status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+		assert!(client.home().await.unwrap_err().is_decode());
+		client.home().await.unwrap();
+		client.production().await.unwrap_err();
+		client.production_summary().await.unwrap();
+		server.clear_faults();
+		client.production().await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_mock_latency() {
+		let server = mock::MockEnvoy::start().await.unwrap();
+		let client = server.client();
+		server.set_latency(std::time::Duration::from_millis(200));
+		tokio::time::timeout(std::time::Duration::from_millis(50), client.home()).await.unwrap_err();
+		server.set_latency(std::time::Duration::ZERO);
+		server.inject("/info.xml", mock::Fault::Delay(std::time::Duration::from_millis(200)));
+		tokio::time::timeout(std::time::Duration::from_millis(50), client.info()).await.unwrap_err();
+		client.home().await.unwrap();
+	}
 }
@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+
+use compact_str::CompactString;
+use serde::Deserialize;
+use serde_with::DeserializeFromStr;
+use strum::EnumString;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommLevel {
+	pub serial_num: CompactString,
+	pub level: u8
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, EnumString, DeserializeFromStr)]
+pub(crate) enum CommCheckStatus {
+	#[strum(serialize = "idle")]
+	Idle,
+	#[strum(serialize = "running")]
+	Running,
+	#[strum(serialize = "complete")]
+	Complete,
+	/// Any other status (e.g. `"failed"`) ends the check without it completing.
+	#[strum(default)]
+	Other(String)
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+pub(crate) struct CommCheckResponse {
+	pub status: CommCheckStatus,
+	results: BTreeMap<CompactString, u8>
+}
+
+impl CommCheckResponse {
+	#[inline]
+	pub fn into_levels(self) -> Vec<CommLevel> {
+		self.results.into_iter().map(|(serial_num, level)| CommLevel { serial_num, level }).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_deserialize_running() {
+		let s = include_str!("comm_check/testdata/running.json");
+		let response: CommCheckResponse = serde_json::from_str(s).unwrap();
+		assert_eq!(response.status, CommCheckStatus::Running);
+		assert_eq!(response.into_levels(), vec![]);
+	}
+
+	#[test]
+	fn test_deserialize_failed() {
+		let s = include_str!("comm_check/testdata/failed.json");
+		let response: CommCheckResponse = serde_json::from_str(s).unwrap();
+		assert_eq!(response.status, CommCheckStatus::Other("failed".into()));
+		assert_eq!(response.into_levels(), vec![CommLevel { serial_num: "121816047176".into(), level: 5 }]);
+	}
+
+	#[test]
+	fn test_deserialize_complete() {
+		let s = include_str!("comm_check/testdata/complete.json");
+		let response: CommCheckResponse = serde_json::from_str(s).unwrap();
+		assert_eq!(response.status, CommCheckStatus::Complete);
+		assert_eq!(
+			response.into_levels(),
+			vec![
+				CommLevel { serial_num: "121816047176".into(), level: 5 },
+				CommLevel { serial_num: "121817001633".into(), level: 0 },
+				CommLevel { serial_num: "121817002899".into(), level: 4 },
+			]
+		);
+	}
+}
@@ -0,0 +1,93 @@
+//! Default credentials for Envoys running pre-7.x firmware, which are derived from the serial
+//! number rather than set by the owner.
+
+use super::Client;
+use super::InfoError;
+
+pub const INSTALLER_USERNAME: &str = "installer";
+pub const OWNER_USERNAME: &str = "envoy";
+const REALM: &str = "enphaseenergy.com";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DerivedCredentialsError {
+	#[error("Invalid base URL: {0}")]
+	Url(#[from] url::ParseError),
+	#[error("Failed to retrieve serial number: {0}")]
+	Info(#[from] InfoError)
+}
+
+/// Derives the installer password for the Envoy with the given serial number.
+pub fn installer_password(serial_number: &str) -> String {
+	let digest = format!("{:x}", md5::compute(format!("[e]{INSTALLER_USERNAME}@{REALM}#{serial_number} EnPhAsE eNeRgY ")));
+	let mut zeroes = digest.bytes().filter(|&c| c == b'0').count() as i32;
+	let mut ones = digest.bytes().filter(|&c| c == b'1').count() as i32;
+	digest
+		.bytes()
+		.rev()
+		.take(8)
+		.map(|c| {
+			if (matches!(zeroes, 3 | 6 | 9)) {
+				zeroes -= 1;
+			}
+			zeroes = zeroes.clamp(0, 20);
+			if (matches!(ones, 9 | 15)) {
+				ones -= 1;
+			}
+			ones = ones.clamp(0, 26);
+			match c {
+				b'0' => {
+					let c = (b'f' + zeroes as u8) as char;
+					zeroes -= 1;
+					c
+				},
+				b'1' => {
+					let c = (b'@' + ones as u8) as char;
+					ones -= 1;
+					c
+				},
+				c => c as char
+			}
+		})
+		.collect()
+}
+
+/// The default owner password is the last six digits of the serial number.
+pub fn owner_password(serial_number: &str) -> &str {
+	let start = serial_number.char_indices().rev().nth(5).map_or(0, |(i, _)| i);
+	&serial_number[start..]
+}
+
+impl Client {
+	/// Fetches `info.xml` to learn the serial number, then returns a client using the derived
+	/// installer credentials.
+	pub async fn with_installer_credentials(base_url: impl AsRef<str>) -> Result<Self, DerivedCredentialsError> {
+		let serial_number = Self::new(base_url.as_ref(), "", "")?.info().await?.device.serial_number;
+		Ok(Self::new(base_url, INSTALLER_USERNAME, installer_password(&serial_number))?)
+	}
+
+	/// Fetches `info.xml` to learn the serial number, then returns a client using the default
+	/// owner credentials.
+	pub async fn with_owner_credentials(base_url: impl AsRef<str>) -> Result<Self, DerivedCredentialsError> {
+		let serial_number = Self::new(base_url.as_ref(), "", "")?.info().await?.device.serial_number;
+		Ok(Self::new(base_url, OWNER_USERNAME, owner_password(&serial_number))?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_installer_password() {
+		assert_eq!(installer_password("121915008901"), "9Cb4h9dB");
+		assert_eq!(installer_password("121008012345"), "7aebd348");
+		assert_eq!(installer_password("122133041234"), "c57h92bf");
+	}
+
+	#[test]
+	fn test_owner_password() {
+		assert_eq!(owner_password("121915008901"), "008901");
+		assert_eq!(owner_password("1234"), "1234");
+		assert_eq!(owner_password("12191500890é"), "00890é");
+	}
+}
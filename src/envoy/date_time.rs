@@ -0,0 +1,47 @@
+use chrono::serde::ts_seconds;
+use chrono::serde::ts_seconds_option;
+use chrono::DateTime;
+use chrono::Utc;
+use compact_str::CompactString;
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+pub struct DateTimeConfig {
+	#[serde(rename = "tz")]
+	pub timezone: CompactString,
+	pub date: CompactString,
+	pub time: CompactString,
+	#[serde(with = "ts_seconds")]
+	pub epoch: DateTime<Utc>,
+	pub ntp_enabled: bool,
+	pub ntp_server: CompactString,
+	pub ntp_synced: bool,
+	#[serde(with = "ts_seconds_option")]
+	pub last_ntp_sync: Option<DateTime<Utc>>
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::TimeZone;
+
+	use super::*;
+
+	#[test]
+	fn test_deserialize_date_time() {
+		let s = include_str!("date_time/testdata/date_time_display.json");
+		let config: DateTimeConfig = serde_json::from_str(s).unwrap();
+		assert_eq!(
+			config,
+			DateTimeConfig {
+				timezone: "US/Eastern".into(),
+				date: "12/12/2022".into(),
+				time: "14:36".into(),
+				epoch: Utc.timestamp_opt(1670873760, 0).unwrap(),
+				ntp_enabled: true,
+				ntp_server: "time.enphaseenergy.com".into(),
+				ntp_synced: true,
+				last_ntp_sync: Some(Utc.timestamp_opt(1670870160, 0).unwrap())
+			}
+		);
+	}
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use compact_str::CompactString;
+use futures::stream;
+use futures::StreamExt;
+use ipnet::IpNet;
+use mdns_sd::ServiceDaemon;
+use mdns_sd::ServiceEvent;
+use tokio::time::Instant;
+use url::Url;
+
+use super::Client;
+
+const SERVICE_TYPE: &str = "_enphase-envoy._tcp.local.";
+/// Ranges with more host bits than this (a `/16` for IPv4) are refused rather than probed.
+const MAX_HOST_BITS: u8 = 16;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DiscoveredEnvoy {
+	pub base_url: Url,
+	pub serial_number: CompactString,
+	pub software: CompactString
+}
+
+#[derive(Clone, Debug)]
+pub struct DiscoveryOptions {
+	/// How long to listen for mDNS announcements; `None` skips mDNS entirely.
+	pub mdns_timeout: Option<Duration>,
+	/// Address ranges to probe for `info.xml`, in addition to anything found over mDNS.
+	pub cidrs: Vec<IpNet>,
+	/// The HTTP port probed on each host in `cidrs`.
+	pub probe_port: u16,
+	pub probe_timeout: Duration,
+	pub probe_concurrency: usize
+}
+
+impl Default for DiscoveryOptions {
+	#[inline]
+	fn default() -> Self {
+		Self {
+			mdns_timeout: Some(Duration::from_secs(3)),
+			cidrs: Vec::new(),
+			probe_port: 80,
+			probe_timeout: Duration::from_secs(2),
+			probe_concurrency: 32
+		}
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DiscoveryError {
+	#[error("mDNS error: {0}")]
+	Mdns(#[from] mdns_sd::Error),
+	#[error("No Envoy with serial number {0} found")]
+	NotFound(CompactString),
+	#[error("Address range {0} is too large to probe")]
+	RangeTooLarge(IpNet)
+}
+
+async fn browse_mdns(timeout: Duration) -> Result<BTreeSet<(IpAddr, u16)>, DiscoveryError> {
+	let daemon = ServiceDaemon::new()?;
+	let receiver = daemon.browse(SERVICE_TYPE)?;
+	let deadline = Instant::now() + timeout;
+	let mut found = BTreeSet::new();
+	while let Ok(Ok(event)) = tokio::time::timeout_at(deadline, receiver.recv_async()).await {
+		if let ServiceEvent::ServiceResolved(info) = event {
+			found.extend(info.get_addresses().iter().map(|address| (*address, info.get_port())));
+		}
+	}
+	// Failing to shut the daemon down cleanly only leaks its thread; discovery itself succeeded.
+	let _ = daemon.shutdown();
+	Ok(found)
+}
+
+fn base_url(address: IpAddr, port: u16) -> String {
+	match address {
+		IpAddr::V4(v4) => format!("http://{v4}:{port}/"),
+		IpAddr::V6(v6) => format!("http://[{v6}]:{port}/")
+	}
+}
+
+async fn probe(base_url: String, timeout: Duration) -> Option<DiscoveredEnvoy> {
+	let client = Client::new(base_url, "", "").ok()?;
+	let info = tokio::time::timeout(timeout, client.info()).await.ok()?.ok()?;
+	Some(DiscoveredEnvoy {
+		base_url: client.base_url,
+		serial_number: info.device.serial_number,
+		software: info.device.software
+	})
+}
+
+/// Finds Envoys on the local network by browsing for `_enphase-envoy._tcp` over mDNS and
+/// probing every host in `options.cidrs` for `info.xml`.  Results are deduplicated by serial
+/// number.
+pub async fn discover(options: &DiscoveryOptions) -> Result<Vec<DiscoveredEnvoy>, DiscoveryError> {
+	if let Some(cidr) = options.cidrs.iter().find(|cidr| cidr.max_prefix_len() - cidr.prefix_len() > MAX_HOST_BITS) {
+		return Err(DiscoveryError::RangeTooLarge(*cidr));
+	}
+	let announced = match options.mdns_timeout {
+		Some(timeout) => browse_mdns(timeout).await?,
+		None => BTreeSet::new()
+	};
+	// Hosts are generated as the probes go, rather than all up front.
+	let probed = options.cidrs.iter().flat_map(IpNet::hosts).map(|address| (address, options.probe_port));
+
+	let found: BTreeMap<_, _> = stream::iter(announced.into_iter().chain(probed))
+		.map(|(address, port)| probe(base_url(address, port), options.probe_timeout))
+		.buffer_unordered(options.probe_concurrency.max(1))
+		.filter_map(|envoy| async move { envoy.map(|envoy| (envoy.serial_number.clone(), envoy)) })
+		.collect()
+		.await;
+	Ok(found.into_values().collect())
+}
+
+impl Client {
+	/// Discovers the Envoy with the given serial number and returns a client for it.
+	pub async fn find(serial_number: &str, username: impl AsRef<str>, password: impl AsRef<str>, options: &DiscoveryOptions) -> Result<Self, DiscoveryError> {
+		let envoy = discover(options)
+			.await?
+			.into_iter()
+			.find(|envoy| envoy.serial_number == serial_number)
+			.ok_or_else(|| DiscoveryError::NotFound(serial_number.into()))?;
+		// The URL was already parsed successfully during discovery.
+		Ok(Self::new(envoy.base_url, username, password).unwrap())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_base_url() {
+		assert_eq!(base_url(IpAddr::from([192, 168, 1, 80]), 80), "http://192.168.1.80:80/");
+		assert_eq!(base_url("fe80::1".parse().unwrap(), 8080), "http://[fe80::1]:8080/");
+	}
+
+	#[tokio::test]
+	async fn test_discover_nothing() {
+		let options = DiscoveryOptions {
+			mdns_timeout: None,
+			cidrs: vec!["127.0.0.1/32".parse().unwrap()],
+			probe_timeout: Duration::from_millis(500),
+			..Default::default()
+		};
+		assert_eq!(discover(&options).await.unwrap(), vec![]);
+	}
+
+	#[tokio::test]
+	async fn test_discover_mock() {
+		let server = crate::envoy::mock::MockEnvoy::start().await.unwrap();
+		let options = DiscoveryOptions {
+			mdns_timeout: None,
+			cidrs: vec!["127.0.0.1/32".parse().unwrap()],
+			probe_port: server.client().base_url().port().unwrap(),
+			..Default::default()
+		};
+		let found = discover(&options).await.unwrap();
+		assert_eq!(found.len(), 1);
+		assert_eq!(found[0].serial_number, "121915008901");
+		assert_eq!(found[0].base_url.as_str(), server.base_url());
+	}
+
+	#[tokio::test]
+	async fn test_discover_range_too_large() {
+		for cidr in ["10.0.0.0/8", "fd00::/64"] {
+			let options = DiscoveryOptions {
+				mdns_timeout: None,
+				cidrs: vec![cidr.parse().unwrap()],
+				..Default::default()
+			};
+			assert!(matches!(discover(&options).await, Err(DiscoveryError::RangeTooLarge(_))));
+		}
+	}
+}
@@ -0,0 +1,85 @@
+use chrono::serde::ts_seconds;
+use chrono::DateTime;
+use chrono::Utc;
+use compact_str::CompactString;
+use serde::Deserialize;
+use serde_with::DeserializeFromStr;
+use strum::EnumString;
+
+#[derive(Clone, Debug, Eq, PartialEq, EnumString, DeserializeFromStr)]
+pub enum Severity {
+	#[strum(serialize = "info")]
+	Info,
+	#[strum(serialize = "warning")]
+	Warning,
+	#[strum(serialize = "error")]
+	Error,
+	#[strum(serialize = "critical")]
+	Critical,
+	#[strum(default)]
+	Other(String)
+}
+
+/// A single row of the Envoy event log.  The data-table endpoint returns each row as a JSON
+/// array rather than an object, so field order here must match the column order.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+pub struct Event {
+	pub id: u32,
+	#[serde(with = "ts_seconds")]
+	pub timestamp: DateTime<Utc>,
+	pub serial_num: CompactString,
+	pub code: CompactString,
+	pub severity: Severity,
+	pub description: CompactString
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct EventPage {
+	#[serde(rename = "iTotalRecords")]
+	pub total_records: u32,
+	#[serde(rename = "aaData")]
+	pub events: Vec<Event>
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::TimeZone;
+
+	use super::*;
+
+	#[test]
+	fn test_deserialize_event_page() {
+		let s = include_str!("events/testdata/event_dt.json");
+		let page: EventPage = serde_json::from_str(s).unwrap();
+		assert_eq!(page.total_records, 5);
+		assert_eq!(page.events.len(), 5);
+		assert_eq!(
+			page.events[0],
+			Event {
+				id: 1875,
+				timestamp: Utc.timestamp_opt(1671053563, 0).unwrap(),
+				serial_num: "121816047176".into(),
+				code: "pcu_comm_loss".into(),
+				severity: Severity::Warning,
+				description: "Microinverter failed to report".into()
+			}
+		);
+		assert_eq!(
+			page.events[2],
+			Event {
+				id: 1873,
+				timestamp: Utc.timestamp_opt(1671040211, 0).unwrap(),
+				serial_num: "121915008901".into(),
+				code: "grid_freq_high".into(),
+				severity: Severity::Error,
+				description: "AC Frequency Out Of Range".into()
+			}
+		);
+		assert_eq!(page.events[4].severity, Severity::Critical);
+	}
+
+	#[test]
+	fn test_unknown_severity() {
+		assert_eq!("alert".parse::<Severity>().unwrap(), Severity::Other("alert".into()));
+	}
+}
@@ -0,0 +1,164 @@
+//! Scrapers for first-generation Envoy-C units running R3.x firmware, which only serve HTML
+//! pages.  The scraped values are mapped onto the same types returned by the JSON endpoints.
+
+use chrono::DateTime;
+use chrono::FixedOffset;
+use chrono::NaiveDateTime;
+use chrono::Utc;
+use scraper::ElementRef;
+use scraper::Html;
+use scraper::Selector;
+
+use super::Inverter;
+use super::Summary;
+
+const LAST_REPORT_FORMAT: &str = "%m/%d/%Y %I:%M %p";
+
+#[derive(Debug, thiserror::Error)]
+pub enum LegacyError {
+	#[error("HTTP error: {0}")]
+	Http(#[from] reqwest::Error),
+	#[error("Failed to parse legacy page: {0}")]
+	Parse(String)
+}
+
+fn cell_text(cell: ElementRef<'_>) -> String {
+	cell.text().collect::<String>().trim().to_owned()
+}
+
+fn rows(html: &str) -> Vec<Vec<String>> {
+	let document = Html::parse_document(html);
+	let row_selector = Selector::parse("tr").unwrap();
+	let cell_selector = Selector::parse("td").unwrap();
+	document
+		.select(&row_selector)
+		.map(|row| row.select(&cell_selector).map(cell_text).collect::<Vec<_>>())
+		.filter(|cells| !cells.is_empty())
+		.collect()
+}
+
+fn labelled_value<'a>(rows: &'a [Vec<String>], label: &str) -> Result<&'a str, LegacyError> {
+	rows.iter()
+		.find(|cells| cells.len() >= 2 && cells[0] == label)
+		.map(|cells| cells[1].as_str())
+		.ok_or_else(|| LegacyError::Parse(format!("Missing '{label}' row")))
+}
+
+/// Parses strings like "4.08 kW" or "48.1 MWh" into the base unit (W or Wh).
+fn parse_scaled(s: &str, unit: &str) -> Result<f64, LegacyError> {
+	let mut parts = s.split_whitespace();
+	let (value, suffix) = match (parts.next(), parts.next()) {
+		(Some(value), Some(suffix)) => (value, suffix),
+		_ => return Err(LegacyError::Parse(format!("Malformed quantity '{s}'")))
+	};
+	let value: f64 = value.parse().map_err(|_| LegacyError::Parse(format!("Malformed number '{value}'")))?;
+	let multiplier = match suffix.strip_suffix(unit) {
+		Some("") => 1.0,
+		Some("k") => 1_000.0,
+		Some("M") => 1_000_000.0,
+		Some("G") => 1_000_000_000.0,
+		_ => return Err(LegacyError::Parse(format!("Unexpected unit '{suffix}'")))
+	};
+	Ok(value * multiplier)
+}
+
+/// Builds a [`Summary`] from the `/production` and `/home` pages.  Neither page carries a
+/// timestamp, so the caller supplies the time the pages were fetched.
+pub fn parse_summary(production_html: &str, home_html: &str, reading_time: DateTime<Utc>) -> Result<Summary, LegacyError> {
+	let production = rows(production_html);
+	let home = rows(home_html);
+	let active_count = labelled_value(&home, "Number of Microinverters Online")?;
+	Ok(Summary {
+		active_count: active_count.parse().map_err(|_| LegacyError::Parse(format!("Malformed microinverter count '{active_count}'")))?,
+		reading_time,
+		watts_now: parse_scaled(labelled_value(&production, "Currently")?, "W")? as f32,
+		watt_hours_lifetime: parse_scaled(labelled_value(&production, "Since Installation")?, "Wh")? as u64
+	})
+}
+
+/// Parses the `/inverters` table.  Report times are shown in the Envoy's local time, which the
+/// page does not identify, so the caller supplies its UTC offset.
+pub fn parse_inverters(html: &str, utc_offset: FixedOffset) -> Result<Vec<Inverter>, LegacyError> {
+	rows(html)
+		.into_iter()
+		.map(|cells| {
+			let [serial_number, last_report, current, max] = <[String; 4]>::try_from(cells).map_err(|cells| LegacyError::Parse(format!("Expected 4 inverter columns, found {}", cells.len())))?;
+			let last_report_date = NaiveDateTime::parse_from_str(&last_report, LAST_REPORT_FORMAT)
+				.ok()
+				.and_then(|dt| dt.and_local_timezone(utc_offset).single())
+				.ok_or_else(|| LegacyError::Parse(format!("Malformed report time '{last_report}'")))?;
+			Ok(Inverter {
+				serial_number: serial_number.into(),
+				last_report_date: last_report_date.with_timezone(&Utc),
+				dev_type: 1,
+				last_report_watts: parse_scaled(&current, "W")? as i16,
+				max_report_watts: parse_scaled(&max, "W")? as u16
+			})
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::TimeZone;
+
+	use super::*;
+
+	#[test]
+	fn test_parse_scaled() {
+		assert_eq!(parse_scaled("4.08 kW", "W").unwrap(), 4080.0);
+		assert_eq!(parse_scaled("182 W", "W").unwrap(), 182.0);
+		assert_eq!(parse_scaled("48.1 MWh", "Wh").unwrap(), 48_100_000.0);
+		assert!(parse_scaled("48.1 MW", "Wh").is_err());
+		assert!(parse_scaled("nope", "W").is_err());
+	}
+
+	#[test]
+	fn test_parse_summary() {
+		let production = include_str!("legacy/testdata/production.html");
+		let home = include_str!("legacy/testdata/home.html");
+		let reading_time = Utc.timestamp_opt(1671043140, 0).unwrap();
+		let summary = parse_summary(production, home, reading_time).unwrap();
+		assert_eq!(
+			summary,
+			Summary {
+				active_count: 23,
+				reading_time,
+				watts_now: 4080.0,
+				watt_hours_lifetime: 48_100_000
+			}
+		);
+	}
+
+	#[test]
+	fn test_parse_inverters() {
+		let s = include_str!("legacy/testdata/inverters.html");
+		let inverters = parse_inverters(s, FixedOffset::west_opt(5 * 3600).unwrap()).unwrap();
+		assert_eq!(
+			inverters,
+			vec![
+				Inverter {
+					serial_number: "030912345678".into(),
+					last_report_date: Utc.with_ymd_and_hms(2022, 12, 14, 18, 39, 0).unwrap(),
+					dev_type: 1,
+					last_report_watts: 182,
+					max_report_watts: 198
+				},
+				Inverter {
+					serial_number: "030912345679".into(),
+					last_report_date: Utc.with_ymd_and_hms(2022, 12, 14, 18, 38, 0).unwrap(),
+					dev_type: 1,
+					last_report_watts: 179,
+					max_report_watts: 197
+				},
+				Inverter {
+					serial_number: "030912345680".into(),
+					last_report_date: Utc.with_ymd_and_hms(2022, 12, 13, 21, 52, 0).unwrap(),
+					dev_type: 1,
+					last_report_watts: 0,
+					max_report_watts: 0
+				},
+			]
+		);
+	}
+}
@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use hyper::header;
+use hyper::Body;
+use hyper::Method;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
+
+use crate::mock::response;
+use crate::mock::MockServer;
+use super::Client;
+
+pub const USERNAME: &str = "envoy";
+pub const PASSWORD: &str = "123456";
+const REALM: &str = "enphaseenergy.com";
+
+/// Bundled fixtures: request path (with query, if the client sends one), whether it requires
+/// digest auth, content type, and body.
+const FIXTURES: &[(&str, bool, &str, &str)] = &[
+	("/home.json", false, "application/json", include_str!("home/testdata/home.json")),
+	("/info.xml", false, "text/xml", include_str!("info/testdata/info.xml")),
+	("/inventory.json", false, "application/json", include_str!("inventory/testdata/whole-inventory.json")),
+	("/production.json", false, "application/json", include_str!("production/testdata/production.json")),
+	("/production.json?details=1", false, "application/json", include_str!("production/testdata/production-detail.json")),
+	("/api/v1/production", false, "application/json", include_str!("production/testdata/api-v1-production.json")),
+	("/api/v1/consumption", false, "application/json", include_str!("production/testdata/api-v1-consumption.json")),
+	("/api/v1/production/inverters", true, "application/json", include_str!("inverters/testdata/many.json")),
+	("/installer/pcu_comm_check", true, "application/json", include_str!("comm_check/testdata/complete.json")),
+	("/admin/lib/date_time_display.json", true, "application/json", include_str!("date_time/testdata/date_time_display.json")),
+	("/admin/lib/network_display.json", true, "application/json", include_str!("network_config/testdata/network_display.json")),
+	("/admin/lib/wireless_display.json", true, "application/json", include_str!("wireless/testdata/wireless_display.json")),
+	("/datatab/event_dt.rb", true, "application/json", include_str!("events/testdata/event_dt.json"))
+];
+const COMM_CHECK_PATH: &str = "/installer/pcu_comm_check";
+
+/// Something to go wrong when serving a path.
+#[derive(Clone, Debug)]
+pub enum Fault {
+	/// Respond with this status code and an empty body.
+	Status(u16),
+	/// Respond with only the first half of the fixture body.
+	Truncated,
+	/// Respond with this body instead of the fixture.
+	Body(String),
+	/// Wait this long before responding normally.
+	Delay(Duration)
+}
+
+#[derive(Clone, Debug)]
+struct Fixture {
+	digest_auth: bool,
+	content_type: &'static str,
+	body: String
+}
+
+#[derive(Debug, Default)]
+struct Faults {
+	persistent: BTreeMap<String, Fault>,
+	once: BTreeMap<String, VecDeque<Fault>>
+}
+
+#[derive(Debug)]
+struct State {
+	fixtures: RwLock<BTreeMap<String, Fixture>>,
+	faults: Mutex<Faults>,
+	latency: RwLock<Duration>,
+	nonce: AtomicU64,
+	requests: AtomicU64,
+	/// Polls since the last comm check was started.
+	comm_check_polls: AtomicU64
+}
+
+/// An in-process fake Envoy, listening on an ephemeral localhost port and serving the
+/// bundled `testdata` fixtures.  Endpoints that need digest auth on a real Envoy accept
+/// [`USERNAME`] and [`PASSWORD`].  The server shuts down when this is dropped.
+#[derive(Debug)]
+pub struct MockEnvoy {
+	server: MockServer,
+	state: Arc<State>
+}
+
+impl MockEnvoy {
+	/// Starts the server on the current Tokio runtime.
+	pub async fn start() -> io::Result<Self> {
+		let fixtures = FIXTURES
+			.iter()
+			.map(|(path, digest_auth, content_type, body)| {
+				(
+					path.to_string(),
+					Fixture {
+						digest_auth: *digest_auth,
+						content_type,
+						body: body.to_string()
+					}
+				)
+			})
+			.collect();
+		let state = Arc::new(State {
+			fixtures: RwLock::new(fixtures),
+			faults: Mutex::default(),
+			latency: RwLock::default(),
+			nonce: AtomicU64::new(0),
+			requests: AtomicU64::new(0),
+			comm_check_polls: AtomicU64::new(0)
+		});
+		let server = MockServer::start(state.clone(), handle)?;
+		Ok(Self { server, state })
+	}
+
+	#[inline]
+	pub fn base_url(&self) -> String {
+		format!("http://{}/", self.server.addr())
+	}
+
+	/// A client for this server, using the mock credentials.
+	pub fn client(&self) -> Client {
+		// The mock's own URL always parses.
+		Client::new(self.base_url(), USERNAME, PASSWORD).unwrap()
+	}
+
+	/// Serves `body` for `path` (including the query string, if the client sends one) instead
+	/// of the bundled fixture, e.g. one of the other files under `testdata`.  `digest_auth`
+	/// says whether requests for it must carry the mock credentials.
+	pub fn set_fixture(&self, path: impl Into<String>, digest_auth: bool, body: impl Into<String>) {
+		let path = path.into();
+		let mut fixtures = self.state.fixtures.write().unwrap();
+		let content_type = fixtures.get(&path).map_or("application/json", |f| f.content_type);
+		fixtures.insert(path, Fixture { digest_auth, content_type, body: body.into() });
+	}
+
+	/// Delays every response by `latency`.
+	pub fn set_latency(&self, latency: Duration) {
+		*self.state.latency.write().unwrap() = latency;
+	}
+
+	/// Applies `fault` to every request for `path` until [`MockEnvoy::clear_faults`] is called.
+	pub fn inject(&self, path: impl Into<String>, fault: Fault) {
+		self.state.faults.lock().unwrap().persistent.insert(path.into(), fault);
+	}
+
+	/// Applies `fault` to the next request for `path` only.  Queued faults are used up in
+	/// order, before any persistent fault for the same path.
+	pub fn inject_once(&self, path: impl Into<String>, fault: Fault) {
+		self.state.faults.lock().unwrap().once.entry(path.into()).or_default().push_back(fault);
+	}
+
+	pub fn clear_faults(&self) {
+		*self.state.faults.lock().unwrap() = Faults::default();
+	}
+
+	/// How many requests the server has received, including digest auth challenges.
+	pub fn requests(&self) -> u64 {
+		self.state.requests.load(Ordering::SeqCst)
+	}
+}
+
+fn digest_challenge(state: &State) -> Response<Body> {
+	let nonce = format!("{:x}", md5::compute(state.nonce.fetch_add(1, Ordering::SeqCst).to_be_bytes()));
+	let mut response = response(StatusCode::UNAUTHORIZED, "text/html", "");
+	let challenge = format!(r#"Digest realm="{REALM}", qop="auth", nonce="{nonce}", opaque="", algorithm="MD5""#);
+	// The challenge is built from hex digits and constants.
+	response.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_str(&challenge).unwrap());
+	response
+}
+
+/// Checks an `Authorization: Digest ...` header against the mock credentials.
+fn digest_authorized(method: &str, authorization: &str) -> bool {
+	let Some(params) = authorization.strip_prefix("Digest ") else {
+		return false;
+	};
+	let params: BTreeMap<&str, &str> = params.split(',').filter_map(|param| param.trim().split_once('=')).map(|(k, v)| (k, v.trim_matches('"'))).collect();
+	let param = |name| params.get(name).copied().unwrap_or_default();
+	if (param("username") != USERNAME || param("realm") != REALM) {
+		return false;
+	}
+	let ha1 = md5::compute(format!("{USERNAME}:{REALM}:{PASSWORD}"));
+	let ha2 = md5::compute(format!("{method}:{}", param("uri")));
+	let expected = match param("qop") {
+		"" => md5::compute(format!("{ha1:x}:{}:{ha2:x}", param("nonce"))),
+		qop => md5::compute(format!("{ha1:x}:{}:{}:{}:{qop}:{ha2:x}", param("nonce"), param("nc"), param("cnonce")))
+	};
+	param("response") == format!("{expected:x}")
+}
+
+async fn handle(state: Arc<State>, request: Request<Body>) -> Response<Body> {
+	state.requests.fetch_add(1, Ordering::SeqCst);
+	let latency = *state.latency.read().unwrap();
+	if (!latency.is_zero()) {
+		tokio::time::sleep(latency).await;
+	}
+
+	let uri = request.uri();
+	let path_and_query = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or_else(|| uri.path()).to_owned();
+	let (key, fixture) = {
+		let fixtures = state.fixtures.read().unwrap();
+		match fixtures.get(&path_and_query) {
+			Some(fixture) => (path_and_query, fixture.clone()),
+			None => match fixtures.get(uri.path()) {
+				Some(fixture) => (uri.path().to_owned(), fixture.clone()),
+				None => return response(StatusCode::NOT_FOUND, "text/html", "")
+			}
+		}
+	};
+
+	if (fixture.digest_auth) {
+		let authorization = request.headers().get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()).unwrap_or_default();
+		if (!digest_authorized(request.method().as_str(), authorization)) {
+			return digest_challenge(&state);
+		}
+	}
+
+	let fault = {
+		let mut faults = state.faults.lock().unwrap();
+		match faults.once.get_mut(&key).and_then(VecDeque::pop_front) {
+			Some(fault) => Some(fault),
+			None => faults.persistent.get(&key).cloned()
+		}
+	};
+	match fault {
+		None if key == COMM_CHECK_PATH => comm_check(&state, request.method(), fixture),
+		None => response(StatusCode::OK, fixture.content_type, fixture.body),
+		Some(Fault::Status(status)) => response(StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR), "text/html", ""),
+		Some(Fault::Truncated) => {
+			let mut end = fixture.body.len() / 2;
+			while (!fixture.body.is_char_boundary(end)) {
+				end -= 1;
+			}
+			response(StatusCode::OK, fixture.content_type, fixture.body[..end].to_owned())
+		},
+		Some(Fault::Body(body)) => response(StatusCode::OK, fixture.content_type, body),
+		Some(Fault::Delay(delay)) => {
+			tokio::time::sleep(delay).await;
+			response(StatusCode::OK, fixture.content_type, fixture.body)
+		}
+	}
+}
+
+/// A started comm check reports itself running on the first poll, then serves the fixture.
+fn comm_check(state: &State, method: &Method, fixture: Fixture) -> Response<Body> {
+	let polls = match method == Method::POST {
+		true => state.comm_check_polls.swap(0, Ordering::SeqCst),
+		false => state.comm_check_polls.fetch_add(1, Ordering::SeqCst)
+	};
+	match method == Method::POST || polls == 0 {
+		true => response(StatusCode::OK, fixture.content_type, include_str!("comm_check/testdata/running.json")),
+		false => response(StatusCode::OK, fixture.content_type, fixture.body)
+	}
+}
@@ -0,0 +1,91 @@
+use std::net::IpAddr;
+
+use compact_str::CompactString;
+use macaddr::MacAddr6;
+use serde::Deserialize;
+use serde_with::serde_as;
+use serde_with::DeserializeFromStr;
+use serde_with::DisplayFromStr;
+use strum::EnumString;
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+pub struct NetworkConfig {
+	pub hostname: CompactString,
+	pub primary_interface: CompactString,
+	pub interfaces: Vec<InterfaceConfig>
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, EnumString, DeserializeFromStr)]
+pub enum InterfaceType {
+	#[strum(serialize = "ethernet")]
+	Wired,
+	#[strum(serialize = "wifi")]
+	WiFi,
+	#[strum(default)]
+	Other(String)
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+pub struct InterfaceConfig {
+	#[serde(rename = "type")]
+	pub kind: InterfaceType,
+	pub interface: CompactString,
+	#[serde_as(as = "DisplayFromStr")]
+	pub mac: MacAddr6,
+	pub dhcp: bool,
+	pub ip: IpAddr,
+	pub netmask: IpAddr,
+	pub gateway: IpAddr,
+	pub dns: Vec<IpAddr>,
+	pub carrier: bool
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_deserialize_network_config() {
+		let s = include_str!("network_config/testdata/network_display.json");
+		let config: NetworkConfig = serde_json::from_str(s).unwrap();
+		assert_eq!(
+			config,
+			NetworkConfig {
+				hostname: "envoy".into(),
+				primary_interface: "wlan0".into(),
+				interfaces: vec![
+					InterfaceConfig {
+						kind: InterfaceType::Wired,
+						interface: "eth0".into(),
+						mac: MacAddr6::new(0x00, 0x1d, 0xc0, 0x6d, 0x32, 0xc6),
+						dhcp: false,
+						ip: IpAddr::from([169, 254, 120, 1]),
+						netmask: IpAddr::from([255, 255, 0, 0]),
+						gateway: IpAddr::from([0, 0, 0, 0]),
+						dns: vec![],
+						carrier: false
+					},
+					InterfaceConfig {
+						kind: InterfaceType::WiFi,
+						interface: "wlan0".into(),
+						mac: MacAddr6::new(0x38, 0x81, 0xd7, 0x35, 0x9b, 0xd2),
+						dhcp: true,
+						ip: IpAddr::from([192, 168, 1, 80]),
+						netmask: IpAddr::from([255, 255, 255, 0]),
+						gateway: IpAddr::from([192, 168, 1, 1]),
+						dns: vec![IpAddr::from([192, 168, 1, 1]), IpAddr::from([8, 8, 8, 8])],
+						carrier: true
+					}
+				]
+			}
+		);
+	}
+
+	#[test]
+	fn test_unknown_interface_type() {
+		let s = include_str!("network_config/testdata/network_display.json").replacen("\"wifi\"", "\"cellular\"", 1);
+		let config: NetworkConfig = serde_json::from_str(&s).unwrap();
+		assert_eq!(config.interfaces[1].kind, InterfaceType::Other("cellular".into()));
+	}
+}
@@ -108,6 +108,8 @@ pub struct Detail {
 	#[serde(rename = "pwrFactor")]
 	pub power_factor: f32,
 	pub vah_today: f32,
+	/// Per-phase breakdown; only present when requested with `details=1`.
+	#[serde(default)]
 	pub lines: Vec<Line>
 }
 
@@ -138,6 +140,16 @@ pub struct Line {
 	pub power_factor: f32
 }
 
+/// The lightweight totals served by `/api/v1/production` and `/api/v1/consumption`.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnergyTotals {
+	pub watts_now: i32,
+	pub watt_hours_today: u64,
+	pub watt_hours_seven_days: u64,
+	pub watt_hours_lifetime: u64
+}
+
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Storage {
@@ -171,6 +183,46 @@ mod tests {
 
 	use super::*;
 
+	#[test]
+	fn test_deserialize_energy_totals() {
+		let s = include_str!("production/testdata/api-v1-production.json");
+		let totals: EnergyTotals = serde_json::from_str(s).unwrap();
+		assert_eq!(
+			totals,
+			EnergyTotals {
+				watts_now: 418,
+				watt_hours_today: 4174,
+				watt_hours_seven_days: 83245,
+				watt_hours_lifetime: 57397093
+			}
+		);
+
+		let s = include_str!("production/testdata/api-v1-consumption.json");
+		let totals: EnergyTotals = serde_json::from_str(s).unwrap();
+		assert_eq!(
+			totals,
+			EnergyTotals {
+				watts_now: 1362,
+				watt_hours_today: 21876,
+				watt_hours_seven_days: 162390,
+				watt_hours_lifetime: 41233870
+			}
+		);
+	}
+
+	#[test]
+	fn test_deserialize_production_summary() {
+		let s = include_str!("production/testdata/production.json");
+		let stats: EnergyStats = serde_json::from_str(s).unwrap();
+		let detailed: EnergyStats = serde_json::from_str(include_str!("production/testdata/production-detail.json")).unwrap();
+		assert_eq!(stats.production.summary, detailed.production.summary);
+		assert_eq!(stats.production.detail.lines, vec![]);
+		assert_eq!(stats.production.detail.watts_now, detailed.production.detail.watts_now);
+		assert_eq!(stats.consumption.total.lines, vec![]);
+		assert_eq!(stats.consumption.net.rms_current, detailed.consumption.net.rms_current);
+		assert_eq!(stats.storage, detailed.storage);
+	}
+
 	#[test]
 	fn test_deserialize_production() {
 		let s = include_str!("production/testdata/production-detail.json");
@@ -0,0 +1,129 @@
+use std::net::IpAddr;
+
+use compact_str::CompactString;
+use macaddr::MacAddr6;
+use serde::Deserialize;
+use serde_with::serde_as;
+use serde_with::DeserializeFromStr;
+use serde_with::DisplayFromStr;
+use strum::EnumString;
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+pub struct Wireless {
+	pub supported: bool,
+	pub present: bool,
+	pub configured: bool,
+	pub up: bool,
+	pub carrier: bool,
+	pub selected_region: CompactString,
+	pub current_network: Option<CurrentNetwork>,
+	pub available_networks: Vec<AvailableNetwork>
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, EnumString, DeserializeFromStr)]
+pub enum Security {
+	#[strum(serialize = "none")]
+	Open,
+	#[strum(serialize = "wep")]
+	Wep,
+	#[strum(serialize = "wpa-psk")]
+	WpaPsk,
+	#[strum(serialize = "wpa2-psk")]
+	Wpa2Psk,
+	#[strum(serialize = "wpa3-sae")]
+	Wpa3Sae,
+	#[strum(default)]
+	Other(String)
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+pub struct CurrentNetwork {
+	pub ssid: CompactString,
+	#[serde_as(as = "DisplayFromStr")]
+	pub bssid: MacAddr6,
+	pub status: CompactString,
+	pub security: Security,
+	pub channel: u8,
+	pub rssi: i8,
+	pub signal_strength: u8,
+	pub signal_strength_max: u8,
+	pub ip_address: Option<IpAddr>
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+pub struct AvailableNetwork {
+	pub ssid: CompactString,
+	#[serde_as(as = "DisplayFromStr")]
+	pub bssid: MacAddr6,
+	pub security: Security,
+	pub channel: u8,
+	pub rssi: i8,
+	pub signal_strength: u8
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_deserialize_wireless() {
+		let s = include_str!("wireless/testdata/wireless_display.json");
+		let wireless: Wireless = serde_json::from_str(s).unwrap();
+		assert_eq!(
+			wireless,
+			Wireless {
+				supported: true,
+				present: true,
+				configured: true,
+				up: true,
+				carrier: true,
+				selected_region: "US".into(),
+				current_network: Some(CurrentNetwork {
+					ssid: "HomeNet".into(),
+					bssid: MacAddr6::new(0xf0, 0x9f, 0xc2, 0x11, 0x22, 0x33),
+					status: "connected".into(),
+					security: Security::Wpa2Psk,
+					channel: 6,
+					rssi: -61,
+					signal_strength: 3,
+					signal_strength_max: 5,
+					ip_address: Some(IpAddr::from([192, 168, 1, 80]))
+				}),
+				available_networks: vec![
+					AvailableNetwork {
+						ssid: "HomeNet".into(),
+						bssid: MacAddr6::new(0xf0, 0x9f, 0xc2, 0x11, 0x22, 0x33),
+						security: Security::Wpa2Psk,
+						channel: 6,
+						rssi: -61,
+						signal_strength: 3
+					},
+					AvailableNetwork {
+						ssid: "xfinitywifi".into(),
+						bssid: MacAddr6::new(0xa2, 0x4c, 0x1b, 0x5e, 0x00, 0x12),
+						security: Security::Open,
+						channel: 11,
+						rssi: -84,
+						signal_strength: 1
+					},
+					AvailableNetwork {
+						ssid: "Neighbor 5G".into(),
+						bssid: MacAddr6::new(0x3c, 0x37, 0x86, 0xaa, 0xbb, 0xcc),
+						security: Security::Wpa3Sae,
+						channel: 1,
+						rssi: -77,
+						signal_strength: 2
+					}
+				]
+			}
+		);
+	}
+
+	#[test]
+	fn test_deserialize_unknown_security() {
+		let security: Security = serde_json::from_str("\"wpa2-eap\"").unwrap();
+		assert_eq!(security, Security::Other("wpa2-eap".into()));
+	}
+}
@@ -1,7 +1,33 @@
 #![allow(unused_parens)]
+use std::io;
+
 pub mod cloud;
+pub mod credentials;
 pub mod envoy;
+#[cfg(any(test, feature = "mock", feature = "vcr"))] mod mock;
 mod model;
 pub use model::AggregateProduction;
+pub use model::EnergyInterval;
+pub use model::EnergySummary;
+mod site;
+pub use site::Site;
+pub use site::SiteError;
+pub use site::Source;
+#[cfg(feature = "vcr")] pub mod vcr;
 
 pub(crate) const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Runs `f` on Tokio's blocking thread pool, for file and process I/O reached from async code.
+/// Panics in `f` are propagated; if the runtime is shutting down, an interrupted error is
+/// returned instead.
+pub(crate) async fn unblock<T, E>(f: impl FnOnce() -> Result<T, E> + Send + 'static) -> Result<T, E>
+where
+	T: Send + 'static,
+	E: From<io::Error> + Send + 'static
+{
+	match tokio::task::spawn_blocking(f).await {
+		Ok(result) => result,
+		Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+		Err(e) => Err(io::Error::new(io::ErrorKind::Interrupted, e).into())
+	}
+}
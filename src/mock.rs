@@ -0,0 +1,73 @@
+use std::convert::Infallible;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hyper::header;
+use hyper::server::conn::AddrIncoming;
+use hyper::service::make_service_fn;
+use hyper::service::service_fn;
+use hyper::Body;
+use hyper::Request;
+use hyper::Response;
+use hyper::Server;
+use hyper::StatusCode;
+use tokio::sync::oneshot;
+
+/// A hyper server on an ephemeral localhost port, shared by the cloud and Envoy mocks.  It
+/// shuts down when dropped.
+#[derive(Debug)]
+pub(crate) struct MockServer {
+	addr: SocketAddr,
+	shutdown: Option<oneshot::Sender<()>>
+}
+
+impl MockServer {
+	/// Spawns the server on the current Tokio runtime, passing every request to `handler`.
+	pub(crate) fn start<S, F>(state: Arc<S>, handler: fn(Arc<S>, Request<Body>) -> F) -> io::Result<Self>
+	where
+		S: Send + Sync + 'static,
+		F: Future<Output = Response<Body>> + Send + 'static
+	{
+		let incoming = AddrIncoming::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).map_err(io::Error::other)?;
+		let addr = incoming.local_addr();
+		let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+		let make_service = make_service_fn(move |_| {
+			let state = state.clone();
+			async move {
+				Ok::<_, Infallible>(service_fn(move |request| {
+					let response = handler(state.clone(), request);
+					async move { Ok::<_, Infallible>(response.await) }
+				}))
+			}
+		});
+		let server = Server::builder(incoming).serve(make_service).with_graceful_shutdown(async {
+			let _ = shutdown_rx.await;
+		});
+		tokio::spawn(server);
+		Ok(Self { addr, shutdown: Some(shutdown) })
+	}
+
+	#[inline]
+	pub(crate) fn addr(&self) -> SocketAddr {
+		self.addr
+	}
+}
+
+impl Drop for MockServer {
+	#[inline]
+	fn drop(&mut self) {
+		if let Some(shutdown) = self.shutdown.take() {
+			let _ = shutdown.send(());
+		}
+	}
+}
+
+#[inline]
+pub(crate) fn response(status: StatusCode, content_type: &'static str, body: impl Into<Body>) -> Response<Body> {
+	let mut response = Response::new(body.into());
+	*response.status_mut() = status;
+	response.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static(content_type));
+	response
+}
@@ -43,3 +43,111 @@ impl From<crate::cloud::MicroinverterProduction> for AggregateProduction {
 		}
 	}
 }
+
+impl From<crate::cloud::LatestTelemetry> for AggregateProduction {
+	#[inline]
+	fn from(raw: crate::cloud::LatestTelemetry) -> Self {
+		let production_meter = raw.meter("production");
+		let timestamp = production_meter.into_iter().chain(raw.micros.iter()).map(|r| r.last_report_at).max();
+		Self {
+			timestamp: timestamp.unwrap_or(DateTime::<Utc>::MIN_UTC),
+			inverters_reporting: raw.micros.len() as u16,
+			instantaneous_power_watts: raw.production_watts()
+		}
+	}
+}
+
+/// Current power and energy totals, whichever API they came from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EnergySummary {
+	pub watts_now: i32,
+	pub watt_hours_today: u64,
+	pub watt_hours_lifetime: u64
+}
+
+impl From<crate::envoy::EnergyTotals> for EnergySummary {
+	#[inline]
+	fn from(raw: crate::envoy::EnergyTotals) -> Self {
+		Self {
+			watts_now: raw.watts_now,
+			watt_hours_today: raw.watt_hours_today,
+			watt_hours_lifetime: raw.watt_hours_lifetime
+		}
+	}
+}
+
+impl From<crate::cloud::SystemSummary> for EnergySummary {
+	#[inline]
+	fn from(raw: crate::cloud::SystemSummary) -> Self {
+		Self {
+			watts_now: raw.current_power as i32,
+			watt_hours_today: raw.energy_today.into(),
+			watt_hours_lifetime: raw.energy_lifetime.into()
+		}
+	}
+}
+
+/// Energy over one telemetry interval, whatever it measures.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EnergyInterval {
+	pub end_at: DateTime<Utc>,
+	/// `None` for series that don't report device counts.
+	pub devices_reporting: Option<u16>,
+	pub watt_hours: i64
+}
+
+impl From<crate::cloud::MicroinverterProduction> for EnergyInterval {
+	#[inline]
+	fn from(raw: crate::cloud::MicroinverterProduction) -> Self {
+		Self {
+			end_at: raw.end_at,
+			devices_reporting: Some(raw.devices_reporting),
+			watt_hours: raw.energy_this_interval_wh.into()
+		}
+	}
+}
+
+impl From<crate::cloud::ConsumptionMeterInterval> for EnergyInterval {
+	#[inline]
+	fn from(raw: crate::cloud::ConsumptionMeterInterval) -> Self {
+		Self {
+			end_at: raw.end_at,
+			devices_reporting: Some(raw.devices_reporting),
+			watt_hours: raw.energy_this_interval_wh.into()
+		}
+	}
+}
+
+impl From<crate::cloud::ProductionMeterInterval> for EnergyInterval {
+	#[inline]
+	fn from(raw: crate::cloud::ProductionMeterInterval) -> Self {
+		Self {
+			end_at: raw.end_at,
+			devices_reporting: Some(raw.devices_reporting),
+			watt_hours: raw.energy_delivered_wh.into()
+		}
+	}
+}
+
+/// Net battery output: positive when the batteries discharged more than they charged.
+impl From<crate::cloud::BatteryInterval> for EnergyInterval {
+	#[inline]
+	fn from(raw: crate::cloud::BatteryInterval) -> Self {
+		Self {
+			end_at: raw.end_at,
+			devices_reporting: Some(cmp::max(raw.charge.devices_reporting, raw.discharge.devices_reporting)),
+			watt_hours: i64::from(raw.discharge.energy_wh) - i64::from(raw.charge.energy_wh)
+		}
+	}
+}
+
+impl From<crate::cloud::GridInterval> for EnergyInterval {
+	#[inline]
+	fn from(raw: crate::cloud::GridInterval) -> Self {
+		Self {
+			end_at: raw.end_at,
+			devices_reporting: None,
+			watt_hours: raw.energy_wh.into()
+		}
+	}
+}
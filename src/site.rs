@@ -0,0 +1,146 @@
+use std::future::Future;
+
+use compact_str::CompactString;
+
+use crate::cloud;
+use crate::envoy;
+use crate::AggregateProduction;
+use crate::EnergySummary;
+
+/// Where a [`Site`] got a reading from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Source {
+	Envoy,
+	Cloud
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SiteError {
+	#[error("Failed to read Envoy info: {0}")]
+	Info(#[from] envoy::InfoError),
+	#[error("Cloud API error: {0}")]
+	Cloud(#[from] cloud::Error),
+	#[error("No cloud system has an Envoy with serial number {0}")]
+	NotFound(CompactString),
+	#[error("Envoy unavailable ({envoy}), and cloud API error: {cloud}")]
+	Unavailable { envoy: diqwest::error::Error, cloud: cloud::Error }
+}
+
+/// An Envoy paired with its cloud system.  Reads are served by the Envoy when it can be
+/// reached, and by the cloud API otherwise.
+pub struct Site {
+	envoy: envoy::Client,
+	system: cloud::System
+}
+
+impl Site {
+	#[inline]
+	pub fn new(envoy: envoy::Client, system: cloud::System) -> Self {
+		Self { envoy, system }
+	}
+
+	/// Looks up the cloud system of the Envoy `envoy` talks to, by its serial number.
+	pub async fn link(envoy: envoy::Client, cloud: &cloud::Client) -> Result<Self, SiteError> {
+		let serial = envoy.info().await?.device.serial_number;
+		match cloud.find_system_by_envoy_serial(&serial).await? {
+			Some(system) => Ok(Self::new(envoy, system)),
+			None => Err(SiteError::NotFound(serial))
+		}
+	}
+
+	#[inline]
+	pub fn envoy(&self) -> &envoy::Client {
+		&self.envoy
+	}
+
+	#[inline]
+	pub fn system(&self) -> &cloud::System {
+		&self.system
+	}
+
+	/// Current production, from the Envoy's inverter reports or the cloud's latest telemetry.
+	pub async fn production(&self) -> Result<(AggregateProduction, Source), SiteError> {
+		let envoy = async { Ok(AggregateProduction::from(self.envoy.inverters().await?.as_slice())) };
+		let cloud = async { Ok(AggregateProduction::from(self.system.get_latest_telemetry().await?)) };
+		Self::fall_back(envoy, cloud).await
+	}
+
+	/// Current power and today's and lifetime production, from the Envoy's production totals or
+	/// the cloud's system summary.
+	pub async fn summary(&self) -> Result<(EnergySummary, Source), SiteError> {
+		let envoy = async { Ok(EnergySummary::from(self.envoy.simple_production().await?)) };
+		let cloud = async { Ok(EnergySummary::from(self.system.get_summary().await?)) };
+		Self::fall_back(envoy, cloud).await
+	}
+
+	/// Lifetime production in watt hours, from the same source as [`Site::summary`].
+	pub async fn lifetime_energy(&self) -> Result<(u64, Source), SiteError> {
+		let (summary, source) = self.summary().await?;
+		Ok((summary.watt_hours_lifetime, source))
+	}
+
+	/// Awaits `envoy`, and only if that fails, `cloud`.
+	async fn fall_back<T>(envoy: impl Future<Output = Result<T, diqwest::error::Error>>, cloud: impl Future<Output = Result<T, cloud::Error>>) -> Result<(T, Source), SiteError> {
+		let envoy = match envoy.await {
+			Ok(value) => return Ok((value, Source::Envoy)),
+			Err(e) => e
+		};
+		match cloud.await {
+			Ok(value) => Ok((value, Source::Cloud)),
+			Err(cloud) => Err(SiteError::Unavailable { envoy, cloud })
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn test_mock_site_falls_back_to_cloud() {
+		let enlighten = cloud::mock::MockEnlighten::start().await.unwrap();
+		let envoy = envoy::mock::MockEnvoy::start().await.unwrap();
+		let site = Site::link(envoy.client(), &enlighten.client()).await.unwrap();
+		assert_eq!(site.system().system_id, cloud::mock::SYSTEM_ID);
+		assert_eq!(site.production().await.unwrap().1, Source::Envoy);
+
+		envoy.inject("/api/v1/production/inverters", envoy::mock::Fault::Status(503));
+		let (production, source) = site.production().await.unwrap();
+		assert_eq!(source, Source::Cloud);
+		assert_eq!(production.instantaneous_power_watts, 3107);
+
+		enlighten.set_fixture("/api/v4/systems/698905956/latest_telemetry", "{}");
+		assert!(matches!(site.production().await, Err(SiteError::Unavailable { .. })));
+	}
+
+	#[tokio::test]
+	async fn test_mock_site_summary_and_lifetime_fall_back() {
+		let enlighten = cloud::mock::MockEnlighten::start().await.unwrap();
+		let envoy = envoy::mock::MockEnvoy::start().await.unwrap();
+		let site = Site::link(envoy.client(), &enlighten.client()).await.unwrap();
+		let (summary, source) = site.summary().await.unwrap();
+		assert_eq!(source, Source::Envoy);
+		assert_eq!(
+			summary,
+			EnergySummary {
+				watts_now: 418,
+				watt_hours_today: 4174,
+				watt_hours_lifetime: 57397093
+			}
+		);
+		assert_eq!(site.lifetime_energy().await.unwrap(), (57397093, Source::Envoy));
+
+		envoy.inject("/api/v1/production", envoy::mock::Fault::Status(503));
+		let (summary, source) = site.summary().await.unwrap();
+		assert_eq!(source, Source::Cloud);
+		assert_eq!(
+			summary,
+			EnergySummary {
+				watts_now: 3107,
+				watt_hours_today: 18230,
+				watt_hours_lifetime: 21542380
+			}
+		);
+		assert_eq!(site.lifetime_energy().await.unwrap(), (21542380, Source::Cloud));
+	}
+}
@@ -0,0 +1,399 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use hyper::body::to_bytes;
+use hyper::header;
+use hyper::Body;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::mock::response;
+use crate::mock::MockServer;
+
+const REDACTED: &str = "REDACTED";
+/// Query and form parameters that carry API keys, OAuth codes or tokens.
+const SECRET_PARAMS: &[&str] = &["key", "code", "refresh_token", "access_token", "client_secret"];
+/// Fields of JSON bodies, at any depth, that carry tokens or passwords.
+const SECRET_FIELDS: &[&str] = &["access_token", "refresh_token", "id_token", "client_secret", "password"];
+/// Response headers that describe the transfer rather than the content, so aren't recorded.
+const SKIPPED_HEADERS: &[&str] = &["connection", "content-encoding", "content-length", "set-cookie", "transfer-encoding"];
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+	pub interactions: Vec<Interaction>
+}
+
+impl Cassette {
+	pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+		serde_json::from_slice(&fs::read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+	}
+
+	pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+		fs::write(path, serde_json::to_vec_pretty(self)?)
+	}
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+	pub request: RecordedRequest,
+	pub response: RecordedResponse
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+	pub method: String,
+	/// Path and query, with secret query parameters redacted.
+	pub uri: String,
+	/// The request body, with secret form parameters or JSON fields redacted.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub body: Option<String>
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+	pub status: u16,
+	#[serde(default)]
+	pub headers: Vec<(String, String)>,
+	pub body: String
+}
+
+fn redact_uri(uri: &hyper::Uri) -> String {
+	match uri.query() {
+		Some(query) => format!("{}?{}", uri.path(), redact_query(query)),
+		None => uri.path().to_owned()
+	}
+}
+
+fn redact_query(query: &str) -> String {
+	url::form_urlencoded::parse(query.as_bytes())
+		.fold(url::form_urlencoded::Serializer::new(String::new()), |mut serializer, (k, v)| {
+			match SECRET_PARAMS.contains(&k.as_ref()) {
+				true => serializer.append_pair(&k, REDACTED),
+				false => serializer.append_pair(&k, &v)
+			};
+			serializer
+		})
+		.finish()
+}
+
+/// Redacts secrets from a JSON body; other bodies are returned as they are.
+fn redact_body(body: String) -> String {
+	let Ok(mut value) = serde_json::from_str::<serde_json::Value>(&body) else {
+		return body;
+	};
+	match redact_value(&mut value) {
+		true => value.to_string(),
+		false => body
+	}
+}
+
+/// Redacts [`SECRET_FIELDS`] in `value` and everything nested in it, returning whether anything
+/// was redacted.
+fn redact_value(value: &mut serde_json::Value) -> bool {
+	let mut redacted = false;
+	match value {
+		serde_json::Value::Object(object) => {
+			for (k, v) in object {
+				if (SECRET_FIELDS.contains(&k.as_str())) {
+					*v = REDACTED.into();
+					redacted = true;
+				} else {
+					redacted |= redact_value(v);
+				}
+			}
+		},
+		serde_json::Value::Array(values) => {
+			for v in values {
+				redacted |= redact_value(v);
+			}
+		},
+		_ => ()
+	}
+	redacted
+}
+
+fn redact_request_body(headers: &header::HeaderMap, body: &[u8]) -> Option<String> {
+	if (body.is_empty()) {
+		return None;
+	}
+	let body = String::from_utf8_lossy(body).into_owned();
+	let form = headers
+		.get(header::CONTENT_TYPE)
+		.is_some_and(|content_type| content_type.as_bytes().starts_with(b"application/x-www-form-urlencoded"));
+	Some(match form {
+		true => redact_query(&body),
+		false => redact_body(body)
+	})
+}
+
+#[derive(Debug)]
+struct RecorderState {
+	client: reqwest::Client,
+	upstream: String,
+	cassette: Mutex<Cassette>
+}
+
+/// A local proxy that forwards every request to an upstream Envoy or Enlighten API and records
+/// each exchange.  Point a client at [`Recorder::base_url`] instead of the upstream URL.
+/// Authorization headers are never recorded, and API keys, OAuth codes and tokens are redacted.
+#[derive(Debug)]
+pub struct Recorder {
+	server: MockServer,
+	state: Arc<RecorderState>
+}
+
+impl Recorder {
+	pub async fn start(upstream: &str) -> io::Result<Self> {
+		let state = Arc::new(RecorderState {
+			client: reqwest::Client::new(),
+			upstream: upstream.trim_end_matches('/').to_owned(),
+			cassette: Mutex::default()
+		});
+		let server = MockServer::start(state.clone(), record)?;
+		Ok(Self { server, state })
+	}
+
+	#[inline]
+	pub fn base_url(&self) -> String {
+		format!("http://{}", self.server.addr())
+	}
+
+	/// Everything recorded so far.
+	pub fn cassette(&self) -> Cassette {
+		self.state.cassette.lock().unwrap().clone()
+	}
+
+	#[inline]
+	pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+		self.cassette().save(path)
+	}
+}
+
+async fn record(state: Arc<RecorderState>, request: Request<Body>) -> Response<Body> {
+	let (parts, body) = request.into_parts();
+	let uri = parts.uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+	let body = match to_bytes(body).await {
+		Ok(body) => body,
+		Err(e) => return response(StatusCode::BAD_REQUEST, "text/plain", e.to_string())
+	};
+	let mut upstream = state.client.request(parts.method.clone(), format!("{}{uri}", state.upstream)).body(body.clone());
+	for (name, value) in &parts.headers {
+		if (name != header::HOST && name != header::CONTENT_LENGTH && name != header::ACCEPT_ENCODING) {
+			upstream = upstream.header(name, value);
+		}
+	}
+	let upstream = match upstream.send().await {
+		Ok(upstream) => upstream,
+		Err(e) => return response(StatusCode::BAD_GATEWAY, "text/plain", e.to_string())
+	};
+
+	let status = upstream.status();
+	let headers: Vec<(String, String)> = upstream
+		.headers()
+		.iter()
+		.filter(|(name, _)| !SKIPPED_HEADERS.contains(&name.as_str()))
+		.filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+		.collect();
+	let response_body = match upstream.text().await {
+		Ok(body) => body,
+		Err(e) => return response(StatusCode::BAD_GATEWAY, "text/plain", e.to_string())
+	};
+
+	let interaction = Interaction {
+		request: RecordedRequest {
+			method: parts.method.to_string(),
+			uri: redact_uri(&parts.uri),
+			body: redact_request_body(&parts.headers, &body)
+		},
+		response: RecordedResponse {
+			status: status.as_u16(),
+			headers,
+			body: redact_body(response_body.clone())
+		}
+	};
+	let replayed = replay_response(&interaction.response);
+	state.cassette.lock().unwrap().interactions.push(interaction);
+	// The client gets the real, unredacted body.
+	let (parts, _) = replayed.into_parts();
+	Response::from_parts(parts, Body::from(response_body))
+}
+
+fn replay_response(recorded: &RecordedResponse) -> Response<Body> {
+	let mut response = Response::new(Body::from(recorded.body.clone()));
+	*response.status_mut() = StatusCode::from_u16(recorded.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+	for (name, value) in &recorded.headers {
+		if let (Ok(name), Ok(value)) = (header::HeaderName::try_from(name.as_str()), header::HeaderValue::try_from(value.as_str())) {
+			response.headers_mut().append(name, value);
+		}
+	}
+	response
+}
+
+#[derive(Debug)]
+struct PlayerState {
+	interactions: Mutex<Vec<(Interaction, bool)>>
+}
+
+/// A local server that answers requests from a [`Cassette`].  Each request is matched by
+/// method and (redacted) URI to the first recorded interaction not yet played back; once all
+/// matching interactions have been played, the last one is repeated.
+#[derive(Debug)]
+pub struct Player {
+	server: MockServer
+}
+
+impl Player {
+	pub async fn start(cassette: Cassette) -> io::Result<Self> {
+		let state = Arc::new(PlayerState {
+			interactions: Mutex::new(cassette.interactions.into_iter().map(|interaction| (interaction, false)).collect())
+		});
+		Ok(Self { server: MockServer::start(state, play)? })
+	}
+
+	#[inline]
+	pub fn base_url(&self) -> String {
+		format!("http://{}", self.server.addr())
+	}
+}
+
+async fn play(state: Arc<PlayerState>, request: Request<Body>) -> Response<Body> {
+	let method = request.method().to_string();
+	let uri = redact_uri(request.uri());
+	let mut interactions = state.interactions.lock().unwrap();
+	let mut matching: Vec<_> = interactions
+		.iter_mut()
+		.filter(|(interaction, _)| interaction.request.method == method && interaction.request.uri == uri)
+		.collect();
+	let Some((last, _)) = matching.last() else {
+		return response(StatusCode::NOT_FOUND, "text/plain", format!("No recorded interaction for {method} {uri}"));
+	};
+	let last = last.response.clone();
+	match matching.iter_mut().find(|(_, played)| !*played) {
+		Some((interaction, played)) => {
+			*played = true;
+			replay_response(&interaction.response)
+		},
+		None => replay_response(&last)
+	}
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Mode {
+	/// Forward requests upstream and record them to the cassette at this path.
+	Record(PathBuf),
+	/// Answer requests from the cassette at this path without contacting the upstream.
+	Replay(PathBuf)
+}
+
+/// The [`Recorder`] or [`Player`] that a client was pointed at by `with_vcr`.  It must outlive
+/// the client's requests; call [`Vcr::save`] to write a recording out.
+#[derive(Debug)]
+pub enum Vcr {
+	Recording(Recorder, PathBuf),
+	Replaying(Player)
+}
+
+impl Vcr {
+	pub async fn start(mode: Mode, upstream: &str) -> io::Result<Self> {
+		Ok(match mode {
+			Mode::Record(path) => Self::Recording(Recorder::start(upstream).await?, path),
+			Mode::Replay(path) => Self::Replaying(Player::start(Cassette::load(path)?).await?)
+		})
+	}
+
+	#[inline]
+	pub fn base_url(&self) -> String {
+		match self {
+			Self::Recording(recorder, _) => recorder.base_url(),
+			Self::Replaying(player) => player.base_url()
+		}
+	}
+
+	/// Writes everything recorded so far to the cassette path; does nothing when replaying.
+	pub fn save(&self) -> io::Result<()> {
+		match self {
+			Self::Recording(recorder, path) => recorder.save(path),
+			Self::Replaying(_) => Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+
+	use super::*;
+	use crate::cloud;
+	use crate::credentials::Credentials;
+	use crate::envoy;
+
+	#[tokio::test]
+	async fn test_envoy_record_and_replay() {
+		let envoy = envoy::mock::MockEnvoy::start().await.unwrap();
+		let path = env::temp_dir().join(format!("enphase-test-cassette-{}.json", std::process::id()));
+		let client = envoy::Client::new(envoy.base_url(), envoy::mock::USERNAME, envoy::mock::PASSWORD).unwrap();
+		let (client, vcr) = client.with_vcr(Mode::Record(path.clone())).await.unwrap();
+		let home = client.home().await.unwrap();
+		let inverters = client.inverters().await.unwrap();
+		vcr.save().unwrap();
+		drop(vcr);
+		drop(envoy);
+
+		let recorded = fs::read_to_string(&path).unwrap();
+		assert!(!recorded.contains(envoy::mock::PASSWORD));
+		let client = envoy::Client::new("http://envoy.invalid", envoy::mock::USERNAME, envoy::mock::PASSWORD).unwrap();
+		let (client, vcr) = client.with_vcr(Mode::Replay(path.clone())).await.unwrap();
+		fs::remove_file(&path).unwrap();
+		assert_eq!(client.home().await.unwrap().software_build_epoch, home.software_build_epoch);
+		assert_eq!(client.inverters().await.unwrap().len(), inverters.len());
+		client.inventory().await.unwrap_err();
+		vcr.save().unwrap();
+	}
+
+	#[test]
+	fn test_redact_body() {
+		assert_eq!(
+			redact_body(r#"{"data":[{"refresh_token":"r","nested":{"client_secret":"s"}}],"name":"n"}"#.to_owned()),
+			r#"{"data":[{"nested":{"client_secret":"REDACTED"},"refresh_token":"REDACTED"}],"name":"n"}"#
+		);
+		assert_eq!(redact_body(r#"{"name":"n"}"#.to_owned()), r#"{"name":"n"}"#);
+		assert_eq!(redact_body("not json".to_owned()), "not json");
+	}
+
+	#[test]
+	fn test_redact_request_body() {
+		let mut headers = header::HeaderMap::new();
+		assert_eq!(redact_request_body(&headers, b""), None);
+		assert_eq!(redact_request_body(&headers, br#"{"password":"p"}"#).unwrap(), r#"{"password":"REDACTED"}"#);
+		headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/x-www-form-urlencoded"));
+		assert_eq!(redact_request_body(&headers, b"grant_type=refresh_token&refresh_token=r").unwrap(), "grant_type=refresh_token&refresh_token=REDACTED");
+	}
+
+	#[tokio::test]
+	async fn test_cloud_record_redacts_secrets() {
+		let enlighten = cloud::mock::MockEnlighten::start().await.unwrap();
+		let recorder = Recorder::start(&enlighten.base_url()).await.unwrap();
+		let provider = Arc::new(Credentials::new(cloud::mock::CLIENT_ID, cloud::mock::CLIENT_SECRET));
+		let client = cloud::Client::oauth_with_base_url(&recorder.base_url(), cloud::mock::API_KEY, provider, cloud::mock::AUTHORIZATION_CODE, cloud::oauth::DEFAULT_REDIRECT_URI)
+			.await
+			.unwrap();
+		let systems = client.list_systems().await.unwrap();
+
+		let cassette = recorder.cassette();
+		let recorded = serde_json::to_string(&cassette).unwrap();
+		for secret in [cloud::mock::API_KEY, cloud::mock::AUTHORIZATION_CODE, client.tokens().access(), client.tokens().refresh()] {
+			assert!(!recorded.contains(secret), "{secret} was recorded");
+		}
+
+		let player = Player::start(cassette).await.unwrap();
+		let client = client.with_base_url(&player.base_url());
+		assert_eq!(client.list_systems().await.unwrap().len(), systems.len());
+	}
+}